@@ -1,29 +1,59 @@
-use crate::{exit, ws::PATH, Config, Session};
+use crate::{
+    ctl, exit, init, paths, validate,
+    ws::{CONFIG_DIR, DATA_DIR},
+    Config,
+};
 use log::{error, info, warn};
-use std::fs::{read_dir, remove_file};
+use std::{
+    fs::{read_dir, remove_file},
+    path::Path,
+};
 
-pub(crate) fn parse_args(args: Vec<String>) {
-    if args.is_empty() {
-        return;
-    }
+pub(crate) async fn parse_args(args: Vec<String>) {
+    let args = paths::strip_flags(args);
     let parseable = args.iter().skip(1);
     for (e, arg) in parseable.enumerate() {
         match arg.as_str() {
             "help" => {
                 println!(
                     "valid arguments
+    init        | write a config, with a session for each tmux session running a server
+    ctl         | run a websocket command on the running taurus, e.g. `taurus ctl list-backups`
     check       | check config
     backup      | operate on backups
-    help        | this menu"
+    help        | this menu
+
+options
+    --config-dir <dir>  | config.toml and servers/, also TAURUS_CONFIG_DIR
+    --data-dir <dir>    | history, player data and exports, also TAURUS_DATA_DIR
+
+config: {}
+data: {}",
+                    CONFIG_DIR.display(),
+                    DATA_DIR.display()
                 );
                 exit!();
             }
-            "check" => {
-                let _: Config = Config::load_config(PATH.clone());
-                let _: Vec<Session> = Config::load_sessions(PATH.to_owned());
-                info!("check successful, exiting");
+            "ctl" => {
+                ctl::run(&args[e + 2..]).await;
+                exit!();
+            }
+            "init" => {
+                init::run(&args[e + 2..]);
                 exit!();
             }
+            "check" => {
+                let errors = validate::check_all(&CONFIG_DIR);
+                if errors.is_empty() {
+                    println!("config is valid");
+                    exit!();
+                }
+                for e in &errors {
+                    eprintln!("{e}");
+                }
+                eprintln!("found {} problems", errors.len());
+                std::process::exit(1);
+            }
             "backup" => {
                 if args.len() == 2 || args.len() > 3 {
                     println!(
@@ -36,7 +66,7 @@ example usage:
                     exit!();
                 }
 
-                let config = Config::load_config(PATH.clone());
+                let config = Config::load_config(&CONFIG_DIR);
                 match args[e + 1].as_str() {
                     "ls" => {
                         let mut backups = "backups: ".to_string();
@@ -76,7 +106,7 @@ example usage:
                             exit!();
                         }
 
-                        match remove_file(config.backup_location + &args[e + 2]) {
+                        match remove_file(Path::new(&config.backup_location).join(&args[e + 2])) {
                             Ok(_) => {}
                             Err(e) => {
                                 error!("failed to remove file due to: {e}");
@@ -1,7 +1,22 @@
-use crate::{bridge::Session, utils::Sys, ws::CONFIG};
+use crate::{
+    bridge::Session,
+    commands::CommandRule,
+    files,
+    parser::LogFlavor,
+    region::{Dimension, World},
+    structure,
+    utils::Sys,
+    ws::CONFIG,
+};
 use chrono::{DateTime, Datelike, Local, Timelike};
 use serde_derive::{Deserialize, Serialize};
-use std::{fs, path::PathBuf, time::SystemTime};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 use tokio::{
     fs::{create_dir_all, remove_file},
     process::Command,
@@ -11,9 +26,9 @@ use tokio::{
 const SLOTTED_BACKUP_EPSILON: u64 = 1800;
 
 // options for a session running a server that contains a chat bridge
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub(crate) struct Game {
-    file_path: Option<String>,
+    pub file_path: Option<String>,
     pub backup_path: Option<String>,
     pub backup_interval: Option<u64>,
     pub backup_keep: Option<u64>,
@@ -22,8 +37,18 @@ pub(crate) struct Game {
     pub daily_slots: Option<u64>,
     pub weekly_slots: Option<u64>,
     pub monthly_slots: Option<u64>,
-    in_game_cmd: Option<bool>,
+    // lets players run commands from chat, e.g. `!playtime`
+    pub in_game_cmd: Option<bool>,
+    // defaults to `!`
+    pub cmd_prefix: Option<String>,
+    // permissions and cooldowns per command, also used to add commands with a fixed reply or
+    // that run a websocket command
+    pub commands: Option<HashMap<String, CommandRule>>,
     pub chat_bridge: Option<bool>,
+    // how to read the piped server output, defaults to vanilla
+    pub log_flavor: Option<LogFlavor>,
+    // regex used by the custom flavor, the `body` group is the message after the log prefix
+    pub log_pattern: Option<String>,
 }
 
 #[derive(Clone)]
@@ -310,72 +335,47 @@ impl Game {
         }
     }
 
-    pub(crate) async fn copy_region(&self, dim: &str, x: i32, y: i32) -> String {
-        if self.file_path.is_none()
-            || CONFIG.read().await.webserver_location.is_none()
-            || CONFIG.read().await.webserver_prefix.is_none()
-        {
-            return "webserver not configured".to_owned();
-        }
-        if let Some(ws_l) = &CONFIG.read().await.webserver_location {
-            let webserver_location = PathBuf::from(ws_l).join(PathBuf::from("region"));
-            if !webserver_location.exists() && fs::create_dir_all(&webserver_location).is_err() {
-                return "Unable to create region folder".to_owned();
-            }
-            let dim_folder = match dim {
-                "OW" => "/region",
-                "NETHER" => "/DIM-1/region",
-                "END" => "/DIM1/region",
-                _ => return "Unexpected region".to_owned(),
-            };
-            let region_name = format!("r.{x}.{y}.mca");
+    pub(crate) fn world(&self) -> Option<World> {
+        self.file_path.as_ref().map(World::new)
+    }
 
-            if let Some(fp) = &self.file_path {
-                let full_path = PathBuf::from(fp)
-                    .join(PathBuf::from(dim_folder))
-                    .join(PathBuf::from(&region_name));
-                if !full_path.exists() {
-                    return "Region does not exists".to_owned();
-                }
-                if fs::copy(full_path, webserver_location).is_err() {
-                    return "Failed to copy region into webserver folder".to_owned();
-                }
-                if let Some(ws_p) = &CONFIG.read().await.webserver_prefix {
-                    return format!("{}/region/{region_name}", ws_p);
-                }
-            }
+    // files such as ops.json or usercache.json that are kept next to the world folder
+    pub(crate) fn server_file(&self, name: &str) -> Option<PathBuf> {
+        let world = PathBuf::from(self.file_path.as_ref()?);
+        Some(world.parent()?.join(name))
+    }
+
+    // signed download link to a copy of a region
+    pub(crate) async fn copy_region(&self, dim: &str, x: i32, y: i32) -> String {
+        let dim = match Dimension::parse(dim) {
+            Some(v) => v,
+            None => return "Unexpected region".to_owned(),
+        };
+        let world = match self.world() {
+            Some(v) => v,
+            None => return "no file path specified".to_owned(),
+        };
+        let full_path = world.region_path(dim, x, y);
+        if !full_path.exists() {
+            return "Region does not exists".to_owned();
         }
-        "no file path specified".to_string()
+        files::export(&full_path).await.unwrap_or_else(|e| e)
     }
 
+    // signed download link to a copy of a structure
     pub(crate) async fn copy_structure(&self, name: &str) -> String {
-        if self.file_path.is_none()
-            || CONFIG.read().await.webserver_location.is_none()
-            || CONFIG.read().await.webserver_prefix.is_none()
-        {
-            return "webserver not configured".to_owned();
+        let fp = match &self.file_path {
+            Some(v) => v,
+            None => return "no file path specified".to_owned(),
+        };
+        if name.contains('/') || name.starts_with('.') {
+            return "Invalid structure name".to_owned();
         }
-        if let Some(ws_l) = &CONFIG.read().await.webserver_location {
-            let webserver_location = PathBuf::from(ws_l).join(PathBuf::from("structure"));
-            if !webserver_location.exists() && fs::create_dir_all(&webserver_location).is_err() {
-                return "Unable to create region folder".to_owned();
-            }
-            if let Some(fp) = &self.file_path {
-                let structure = PathBuf::from(fp)
-                    .join(PathBuf::from("structure"))
-                    .join(PathBuf::from(name));
-                if !structure.exists() {
-                    return "Structure does not exists".to_owned();
-                }
-                if fs::copy(structure, webserver_location).is_err() {
-                    return "Failed to copy structure into webserver folder".to_owned();
-                }
-            }
-            if let Some(ws_p) = &CONFIG.read().await.webserver_prefix {
-                return format!("{}/structure/{name}", ws_p);
-            }
+        let structure = PathBuf::from(fp).join("structure").join(name);
+        if !structure.exists() {
+            return "Structure does not exists".to_owned();
         }
-        "no file path specified".to_string()
+        files::export(&structure).await.unwrap_or_else(|e| e)
     }
 
     #[inline(always)]
@@ -399,16 +399,21 @@ impl Game {
             };
             let mut response = Vec::new();
             for file in structures.flatten() {
-                if let Ok(m) = file.metadata() {
-                    response.push(format!(
-                        "{} ({})",
-                        file.file_name().to_string_lossy(),
-                        Self::bytes_to_human(m.len())
-                    ));
+                let m = match file.metadata() {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+                let mut info = match fs::read(file.path()).map_err(|e| e.to_string()) {
+                    Ok(data) => structure::summary(&data),
+                    Err(e) => Err(e),
                 }
+                .unwrap_or_else(|e| json!({ "error": e }));
+                info["name"] = json!(file.file_name().to_string_lossy());
+                info["file_size"] = json!(Self::bytes_to_human(m.len()));
+                response.push(info);
             }
-            response.sort();
-            return response.join("\n");
+            response.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+            return json!(response).to_string();
         }
         "no configured file path".to_string()
     }
@@ -428,35 +433,172 @@ impl Game {
             };
         }
         tokio::spawn(async move {
-            let world_name = &cwd.iter().next_back().unwrap_or_default().to_string_lossy();
-            let now: DateTime<Local> = Local::now();
-            let backup_name = &format!(
-                "{name}_{:0>4}-{:0>2}-{:0>2}_{:0>2}_{:0>2}_{:0>2}.tar.gz",
-                now.year(),
-                now.month(),
-                now.day(),
-                now.hour(),
-                now.minute(),
-                now.second()
-            );
-            let _ = Command::new("cp")
-                .args([
-                    "-ur",
-                    &cwd.to_string_lossy(),
-                    &format!("{}/", backup_location),
-                ])
-                .kill_on_drop(true)
-                .status()
-                .await;
-            let _ = Command::new("tar")
-                .current_dir(backup_location)
-                .args(["-czf", backup_name, world_name])
-                .kill_on_drop(true)
-                .status()
-                .await;
+            let _ = Self::archive(&cwd, &name, &backup_location).await;
         });
         "starting new backup".to_string()
     }
+
+    // backup the world and wait for it to finish, e.g. before changing the world files
+    pub(crate) async fn backup_now(
+        &self,
+        name: &str,
+        backup_location: &str,
+    ) -> Result<String, String> {
+        let cwd = match &self.file_path {
+            Some(v) => PathBuf::from(v),
+            None => return Err("Unable to reach file path".to_owned()),
+        };
+        Self::archive(&cwd, name, backup_location).await
+    }
+
+    // update the copy of the world in the backup folder, then archive it, returns the name of
+    // the archive
+    async fn archive(cwd: &Path, name: &str, backup_location: &str) -> Result<String, String> {
+        let world_name = &cwd.iter().next_back().unwrap_or_default().to_string_lossy();
+        let now: DateTime<Local> = Local::now();
+        let backup_name = format!(
+            "{name}_{:0>4}-{:0>2}-{:0>2}_{:0>2}_{:0>2}_{:0>2}.tar.gz",
+            now.year(),
+            now.month(),
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let copied = Command::new("cp")
+            .args([
+                "-ur",
+                &cwd.to_string_lossy(),
+                &format!("{}/", backup_location),
+            ])
+            .kill_on_drop(true)
+            .status()
+            .await;
+        if !copied.is_ok_and(|s| s.success()) {
+            return Err("Failed to copy world into the backup folder".to_owned());
+        }
+        let archived = Command::new("tar")
+            .current_dir(backup_location)
+            .args(["-czf", &backup_name, world_name])
+            .kill_on_drop(true)
+            .status()
+            .await;
+        if !archived.is_ok_and(|s| s.success()) {
+            return Err("Failed to archive backup".to_owned());
+        }
+        Ok(backup_name)
+    }
+
+    // resolve `live`, a backup file name or a YYYY-MM-DD date to the latest backup of the
+    // session made on or before that day
+    pub(crate) async fn find_snapshot(&self, name: &str, spec: &str) -> Option<Snapshot> {
+        if spec.eq_ignore_ascii_case("live") {
+            return Some(Snapshot::Live);
+        }
+        let mut locations = vec![CONFIG.read().await.backup_location.clone()];
+        if let Some(v) = &self.backup_path {
+            locations.push(v.to_owned());
+        }
+        // yyyy-mm-dd
+        let date = |v: &str| {
+            v.len() == 10
+                && v.chars().enumerate().all(|(i, c)| {
+                    if i == 4 || i == 7 {
+                        c == '-'
+                    } else {
+                        c.is_ascii_digit()
+                    }
+                })
+        };
+        let is_date = date(spec);
+        let prefix = format!("{name}_");
+        let mut latest: Option<(String, PathBuf)> = None;
+        for location in locations {
+            let dir = PathBuf::from(location);
+            for file in [spec.to_string(), format!("{spec}.tar.gz")] {
+                if !is_date && !file.contains('/') && dir.join(&file).is_file() {
+                    return Some(Snapshot::Backup(dir.join(file)));
+                }
+            }
+            if !is_date {
+                continue;
+            }
+            let backups = match dir.read_dir() {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            for backup in backups.flatten() {
+                let file_name = backup.file_name().to_string_lossy().to_string();
+                // the date has to follow the name, so `smp` doesn't pick up `smp_creative_...`
+                let stamp = match file_name.strip_prefix(&prefix) {
+                    Some(v) if v.ends_with(".tar.gz") && v.get(..10).is_some_and(date) => v,
+                    _ => continue,
+                };
+                if stamp[..10] <= *spec && latest.as_ref().is_none_or(|l| stamp > l.0.as_str()) {
+                    latest = Some((stamp.to_string(), backup.path()));
+                }
+            }
+        }
+        latest.map(|(_, path)| Snapshot::Backup(path))
+    }
+
+    // read a file relative to the world folder, e.g. `data/scoreboard.dat`, from the live world
+    // or out of a backup archive
+    pub(crate) async fn read_world_file(
+        &self,
+        snapshot: &Snapshot,
+        file: &str,
+    ) -> Result<Vec<u8>, String> {
+        let world = match &self.file_path {
+            Some(v) => PathBuf::from(v),
+            None => return Err("no configured file path".to_owned()),
+        };
+        let archive = match snapshot {
+            Snapshot::Live => {
+                return tokio::fs::read(world.join(file))
+                    .await
+                    .map_err(|e| format!("unable to read {file}: {e}"))
+            }
+            Snapshot::Backup(v) => v,
+        };
+        // backups are made from the backup folder, so the archive starts with the world's name
+        let world_name = world
+            .iter()
+            .next_back()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let output = Command::new("tar")
+            .arg("-xzOf")
+            .arg(archive)
+            .arg(format!("{world_name}/{file}"))
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("unable to run tar: {e}"))?;
+        if !output.status.success() {
+            return Err(format!("{file} not found in {}", snapshot.name()));
+        }
+        Ok(output.stdout)
+    }
+}
+
+// a state of a world that files can be read from
+pub(crate) enum Snapshot {
+    Live,
+    Backup(PathBuf),
+}
+
+impl Snapshot {
+    pub(crate) fn name(&self) -> String {
+        match self {
+            Self::Live => "live".to_owned(),
+            Self::Backup(v) => v
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
 }
 
 pub(crate) async fn delete_backups_older_than(name: &str, time: u64, backup_location: &str) {
@@ -1,13 +1,23 @@
-use crate::{backup::Game, config::Rcon, ws::SESSIONS};
+use crate::{
+    backup::Game,
+    component::Component,
+    config::Rcon,
+    event::{Event, EventKind},
+    parser::{clean_line, LogParser},
+    rcon::DEFAULT_RCON_TIMEOUT,
+    routing::Delivery,
+    utils::unix_millis,
+    ws::{BRIDGES, CONFIG, SESSIONS},
+};
 use regex::Regex;
 use serde_derive::{Deserialize, Serialize};
-use std::fmt::Write;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::time::Duration;
 use std::{
     fs::{self, File},
     path::PathBuf,
 };
-use tokio::process::Command;
+use tokio::{net::TcpStream, process::Command, time::timeout};
 
 #[derive(Serialize)]
 pub(crate) struct Bridge {
@@ -15,27 +25,41 @@ pub(crate) struct Bridge {
     pub line: usize,
     pub enabled: Option<bool>,
     pub state: bool,
+    // whether the log says the server is up, None until it has said either way
+    pub running: Option<bool>,
+    // ms timestamp of the last log line or rcon answer
+    #[serde(skip)]
+    pub heard: u64,
+    // the longest silence of the last minute, from the last time heard before it to the first
+    // time after
+    #[serde(skip)]
+    pub quiet: (u64, u64),
+    #[serde(skip)]
+    pub parser: Box<dyn LogParser>,
 }
 
 const MAX_PIPE_LENGTH: usize = 8000;
+// a server starting again logs a few lines before it says so, the longest silence this long
+// before them is taken as the time it was down
+const RESTART_WINDOW: u64 = 60_000;
 
-// poll the log file and check for new messages, match them against a certain pattern to dermine if
-// we need to send anything to the clients
+// poll the log file and run each new line through the session's parser to determine if we need
+// to send anything to the clients
 #[inline(always)]
-pub(crate) async fn update_messages(server: &mut Bridge, pattern: &Regex) -> Option<String> {
+pub(crate) async fn update_messages(server: &mut Bridge) -> Vec<Event> {
+    let mut events = Vec::new();
     if server.enabled == None {
-        return None;
+        return events;
     }
     let file_path: String = format!("/tmp/{}-taurus", server.name);
     if !PathBuf::from(&file_path).exists() {
         gen_pipe(&server.name, false).await;
-        return None;
+        return events;
     }
     let reader = BufReader::new(match File::open(file_path) {
         Ok(v) => v,
-        Err(_) => return None,
+        Err(_) => return events,
     });
-    let mut message = String::new();
     for (i, line) in reader.lines().enumerate() {
         // assign the real number of lines, if the file is empty lines returns 0 by default
         // if there is 1 line, there is still 0 lines due to it being 0 indexed
@@ -48,63 +72,34 @@ pub(crate) async fn update_messages(server: &mut Bridge, pattern: &Regex) -> Opt
             Ok(v) => v,
             Err(_) => continue,
         };
-        if line.len() < 3 {
-            continue;
-        }
-        let mut message_out = String::with_capacity(line.len());
-        let message_chars = line.chars().collect::<Vec<char>>();
-        message_chars.iter().for_each(|c| message_out.push(*c));
-        if message_chars.len() < 34 && !server.state {
-            if let Some(true) = server.enabled {
-                if &message_chars[10..33].iter().collect::<String>() == " [Server thread/INFO]: " {
-                    server.state = true;
-                } else {
-                    return None;
-                }
-            }
-        }
-        if pattern.is_match(&line) {
-            let _ = writeln!(
-                &mut message,
-                "[{}] {}",
-                server.name,
-                message_chars[33..].iter().collect::<String>()
-            );
+        server.hear(unix_millis());
+        let line = clean_line(&line);
+        let body = match server.parser.body(&line) {
+            Some(v) => v,
+            None => continue,
+        };
+        // any line from the server means it is up and can receive messages
+        if let Some(true) = server.enabled {
+            server.state = true;
         }
-        if message_out.len() > 52
-            && &message_chars[10..33].iter().collect::<String>() == " [Server thread/INFO]: "
-        {
-            let list_message = &message_chars[33..].iter().collect::<String>();
-            let list_message: Vec<&str> = list_message.split_ascii_whitespace().collect();
-            if let Some(true) = server.enabled {
-                if message_chars[33..52] == "Stopping the server".chars().collect::<Vec<char>>() {
-                    server.state = false;
-                    continue;
-                }
-            }
-            if list_message.len() < 3 {
-                continue;
-            }
-            if list_message[2] == "has" && list_message.len() > 6 && !list_message[0].contains('<')
-            {
-                message.push_str(&message_out[33..]);
-            }
+        server.running = Some(true);
+        let kind = match server.parser.classify(body) {
+            Some(v) => v,
+            None => continue,
+        };
+        if kind == EventKind::ServerStopping {
+            server.state = false;
+            server.running = Some(false);
         }
+        events.push(Event::new(&server.name, kind));
     }
     // if the log file is above 8k we can reset it to prevent parsing time from building up
     if server.line > MAX_PIPE_LENGTH {
         // reset pipe file and notify
         gen_pipe(&server.name, true).await;
         server.line = 0;
-        return match message.len() {
-            3.. => Some(message),
-            _ => None,
-        };
-    }
-    match message.len() {
-        3.. => Some(message),
-        _ => None,
     }
+    events
 }
 
 // set the initial hashmap value of lines so only new lines are sent
@@ -146,6 +141,53 @@ pub async fn gen_pipe(server_name: &str, rm: bool) {
         .spawn();
 }
 
+// stop piping a tmux session's output and remove the pipe file
+pub async fn close_pipe(server_name: &str) {
+    let _ = Command::new("tmux")
+        .args(["pipe-pane", "-t", server_name])
+        .spawn();
+    let _ = fs::remove_file(format!("/tmp/{server_name}-taurus"));
+}
+
+impl Bridge {
+    // reads a game session's piped output from where the pipe file currently ends, None for
+    // sessions without a game
+    pub(crate) fn new(session: &Session) -> Option<Self> {
+        let game = session.game.as_ref()?;
+        Some(Self {
+            name: session.name.clone(),
+            line: set_lines(&session.name),
+            enabled: game.chat_bridge,
+            state: game.chat_bridge.unwrap_or_default(),
+            running: None,
+            heard: 0,
+            quiet: (0, 0),
+            parser: game
+                .log_flavor
+                .unwrap_or_default()
+                .parser(game.log_pattern.as_deref()),
+        })
+    }
+
+    pub(crate) fn hear(&mut self, time: u64) {
+        let (from, to) = self.quiet;
+        if time.saturating_sub(to) > RESTART_WINDOW || time.saturating_sub(self.heard) >= to - from
+        {
+            self.quiet = (self.heard, time);
+        }
+        self.heard = self.heard.max(time);
+    }
+
+    // the last time the server was surely up, 0 if that isn't known yet. after a long silence it
+    // may have been down and be starting again, so the time before it counts for a minute
+    pub(crate) fn up_until(&self, now: u64) -> u64 {
+        match now.saturating_sub(self.quiet.1) > RESTART_WINDOW {
+            true => self.heard,
+            false => self.quiet.0,
+        }
+    }
+}
+
 // store configuration for each session, description is purely for telling what it is
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct Session {
@@ -170,31 +212,99 @@ macro_rules! send {
     };
 }
 
+// what is known about a server before touching its world files
+#[derive(PartialEq, Eq)]
+pub(crate) enum ServerState {
+    Running,
+    Stopped,
+    Unknown,
+}
+
+// rcon answering counts as hearing from the server
+pub(crate) async fn heard(name: &str) {
+    if let Some(bridge) = BRIDGES.lock().await.iter_mut().find(|b| b.name == name) {
+        bridge.hear(unix_millis());
+    }
+}
+
 impl Session {
+    // the session as sent to clients, the rcon password can come from a secret file or the
+    // environment and isn't theirs to see
+    pub(crate) fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(rcon) = value.get_mut("rcon").and_then(|r| r.as_object_mut()) {
+            rcon.remove("password");
+        }
+        value
+    }
+
+    // only a refused rcon connect, or the log saying it stopped when there is no rcon, counts as
+    // stopped. timeouts, auth failures and the pool's backoff say nothing about the server
+    pub(crate) async fn server_state(&self) -> ServerState {
+        let logged = BRIDGES
+            .lock()
+            .await
+            .iter()
+            .find(|b| b.name == self.name)
+            .and_then(|b| b.running);
+        if logged == Some(true) {
+            return ServerState::Running;
+        }
+        let rcon = match &self.rcon {
+            Some(v) => v,
+            None if logged == Some(false) => return ServerState::Stopped,
+            None => return ServerState::Unknown,
+        };
+        let wait = Duration::from_millis(rcon.timeout.unwrap_or(DEFAULT_RCON_TIMEOUT));
+        match timeout(wait, TcpStream::connect(rcon.address())).await {
+            Ok(Ok(_)) => ServerState::Running,
+            Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => ServerState::Stopped,
+            _ => ServerState::Unknown,
+        }
+    }
+
     // send messages to all servers with a 'game' session
     pub(crate) async fn send_chat(&self, rcon: Option<&Rcon>, message: &str, url: bool) {
-        let lines: Vec<&str> = message.lines().collect();
-        for line in lines {
-            let line = line.replace("MSG ", "");
-            let pos = line.find(']').unwrap_or(0);
-            let msg = match Self::clear_formatting(&line) {
-                Some(v) => v,
-                None => continue,
-            };
-            if pos != 0 && (line[1..pos] == self.name || self.game.is_none()) {
+        for line in message.lines() {
+            let line = line.strip_prefix("MSG ").unwrap_or(line);
+            if line.trim().is_empty() {
                 continue;
             }
-            let message = if url {
-                let link = msg.split_whitespace().collect::<Vec<&str>>();
-                let text = if link.len() == 1 {
-                    "attachment"
-                } else {
-                    &msg[link[0].len()..]
+            let (origin, text) = Self::split_origin(line);
+            let delivery = match &origin {
+                Some(origin) => {
+                    if self.game.is_none() {
+                        continue;
+                    }
+                    match CONFIG.read().await.router.route(origin, &self.name, text) {
+                        Some(v) => v,
+                        None => continue,
+                    }
+                }
+                None => Delivery::default(),
+            };
+            let component = if url {
+                let (link, text) = match text.split_once(' ') {
+                    Some((link, text)) => (link, text.trim()),
+                    None => (text, "attachment"),
                 };
-                format!("tellraw @a {{ \"text\": \"{text}\", \"clickEvent\":{{ \"action\": \"open_url\", \"value\": \"{}\"}} }}", link[0])
+                Component::link(text, link)
             } else {
-                format!("tellraw @a {{ \"text\": \"{msg}\" }}")
+                match origin.as_deref() {
+                    // children inherit the style of their parent so the prefix is kept as a
+                    // sibling of the message
+                    Some(v) => Component::text("")
+                        .append(Component::prefix(
+                            v,
+                            delivery.prefix.as_deref(),
+                            delivery.color.as_deref(),
+                        ))
+                        .append(Component::text(" "))
+                        .append(Component::from_markdown(text)),
+                    None => Component::from_markdown(text),
+                }
             };
+            let message = component.tellraw("@a");
             if let Some(v) = rcon {
                 let _ = v.rcon_send(&message).await;
                 continue;
@@ -203,6 +313,19 @@ impl Session {
         }
     }
 
+    // lines relayed through the bridge start with `[origin]`, the session or frontend they came
+    // from. the line went through replace_formatting, the origin is unescaped so `smp_1` is
+    // still the session's name
+    fn split_origin(line: &str) -> (Option<String>, &str) {
+        if let Some(rest) = line.strip_prefix('[') {
+            if let Some((origin, text)) = rest.split_once(']') {
+                let origin = origin.replace("\\_", "_").replace("\\\"", "\"");
+                return (Some(origin), text.trim_start());
+            }
+        }
+        (None, line)
+    }
+
     pub(crate) async fn send_chat_to_clients(bridges: &Vec<Bridge>, message: &str) {
         for client in &*SESSIONS.read().await {
             send!(client, bridges, message, false);
@@ -215,22 +338,6 @@ impl Session {
         }
     }
 
-    // remove formatting when sending messages to the tmux session
-    #[inline(always)]
-    fn clear_formatting(msg: &str) -> Option<String> {
-        let msg = msg
-            .replace('\\', "")
-            .replace('\n', "\\n")
-            .replace('\r', "\\r")
-            .replace('{', "{{")
-            .replace('}', "}}")
-            .replace('\"', "\\\"");
-        match msg.len() {
-            1.. => Some(msg),
-            _ => None,
-        }
-    }
-
     // send command to tmux session
     #[inline(always)]
     pub fn send_command(name: &str, message: &str) {
@@ -240,3 +347,18 @@ impl Session {
             .spawn();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origin_is_unescaped() {
+        let line = replace_formatting("[smp_1] hello_world");
+        let (origin, text) = Session::split_origin(&line);
+        assert_eq!(origin.as_deref(), Some("smp_1"));
+        // the message stays escaped so markdown leaves it alone
+        assert_eq!(text, "hello\\_world");
+        assert_eq!(Session::split_origin("no origin"), (None, "no origin"));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    bridge::Session,
+    component::Component,
+    event::{Event, EventKind},
+    utils::unix_millis,
+    ws::{handle_response, PLAYERS, SESSIONS},
+};
+use log::warn;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+const DEFAULT_PREFIX: &str = "!";
+// seconds
+const DEFAULT_COOLDOWN: u64 = 5;
+const DEFAULT_BACKUP_COOLDOWN: u64 = 300;
+// anyone in this list can run the command, besides player names
+const OPS: &str = "@ops";
+const EVERYONE: &str = "@all";
+const BUILTIN: [&str; 5] = ["backup", "playtime", "tps", "seen", "help"];
+
+lazy_static::lazy_static! {
+    static ref COOLDOWNS: Mutex<HashMap<(String, String, String), Instant>> =
+        Mutex::new(HashMap::new());
+    static ref FORMATTING: Regex = Regex::new("§.").unwrap();
+}
+
+// how a command in a session may be used
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub(crate) struct CommandRule {
+    // player names, `@ops` for operators of the server or `@all`
+    pub permission: Option<Vec<String>>,
+    // seconds a player has to wait between uses
+    pub cooldown: Option<u64>,
+    // fixed text sent back, markdown and links are formatted like bridged chat
+    pub reply: Option<String>,
+    // websocket command to run, `{session}`, `{player}` and `{args}` are filled in
+    pub ws: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+impl CommandRule {
+    // commands that change things on the server are for ops unless configured otherwise
+    fn permission(&self, name: &str) -> Vec<String> {
+        match &self.permission {
+            Some(v) => v.clone(),
+            None if name == "backup" || self.ws.is_some() => vec![OPS.to_string()],
+            None => vec![EVERYONE.to_string()],
+        }
+    }
+
+    fn cooldown(&self, name: &str) -> u64 {
+        self.cooldown.unwrap_or(match name {
+            "backup" => DEFAULT_BACKUP_COOLDOWN,
+            _ => DEFAULT_COOLDOWN,
+        })
+    }
+}
+
+// look for a command in a chat event and run it in the background so slow commands (backups,
+// rcon) don't hold up the bridge
+pub(crate) async fn handle(event: &Event) {
+    let (player, message) = match &event.kind {
+        EventKind::Chat { player, message } => (player, message),
+        _ => return,
+    };
+    // the name ends up in a tellraw selector, anything else could inject into the command
+    if !player
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return;
+    }
+    let session = match SESSIONS
+        .read()
+        .await
+        .iter()
+        .find(|s| s.name == event.session)
+    {
+        Some(v) => v.clone(),
+        None => return,
+    };
+    let game = match &session.game {
+        Some(v) if v.in_game_cmd == Some(true) => v,
+        _ => return,
+    };
+    let prefix = game
+        .cmd_prefix
+        .clone()
+        .unwrap_or_else(|| DEFAULT_PREFIX.to_string());
+    let (name, args) = match message.trim().strip_prefix(&prefix) {
+        Some(v) => v.split_once(' ').unwrap_or((v, "")),
+        None => return,
+    };
+    let name = name.to_lowercase();
+    let rule = game
+        .commands
+        .as_ref()
+        .and_then(|c| c.get(&name))
+        .cloned()
+        .unwrap_or_default();
+    if rule.enabled == Some(false)
+        || (!BUILTIN.contains(&name.as_str()) && rule.reply.is_none() && rule.ws.is_none())
+    {
+        return;
+    }
+    let player = player.to_string();
+    let args = args.trim().to_string();
+    tokio::spawn(async move {
+        if !allowed(&session, &player, &rule.permission(&name)) {
+            reply(
+                &session,
+                &player,
+                "you don't have permission to use this command",
+            )
+            .await;
+            return;
+        }
+        let key = (session.name.clone(), player.to_lowercase(), name.clone());
+        let cooldown = Duration::from_secs(rule.cooldown(&name));
+        {
+            let mut cooldowns = COOLDOWNS.lock().await;
+            if let Some(used) = cooldowns.get(&key) {
+                if used.elapsed() < cooldown {
+                    let left = (cooldown - used.elapsed()).as_secs() + 1;
+                    drop(cooldowns);
+                    let text = format!("wait {left}s before using {prefix}{name} again");
+                    reply(&session, &player, &text).await;
+                    return;
+                }
+            }
+            cooldowns.insert(key, Instant::now());
+        }
+        let response = run(&session, &player, &name, &args, &rule).await;
+        reply(&session, &player, &response).await;
+    });
+}
+
+async fn run(
+    session: &Session,
+    player: &str,
+    name: &str,
+    args: &str,
+    rule: &CommandRule,
+) -> String {
+    if let Some(v) = &rule.reply {
+        return v.clone();
+    }
+    if let Some(v) = &rule.ws {
+        let cmd = v
+            .replace("{session}", &session.name)
+            .replace("{player}", player)
+            .replace("{args}", args);
+        return ws_reply(&cmd).await;
+    }
+    match name {
+        "backup" => ws_reply(&format!("BACKUP {}", session.name)).await,
+        "playtime" => playtime(session, if args.is_empty() { player } else { args }).await,
+        "seen" => match args.split_whitespace().next() {
+            Some(v) => seen(v).await,
+            None => "usage: seen <player>".to_string(),
+        },
+        "tps" => tps(session).await,
+        _ => help(session, player),
+    }
+}
+
+// run a websocket command and drop the command name from its response
+async fn ws_reply(cmd: &str) -> String {
+    let response = match handle_response(cmd).await {
+        Some(v) => v,
+        None => return "done".to_string(),
+    };
+    let name = cmd.split(' ').next().unwrap_or_default();
+    match response.strip_prefix(name) {
+        Some(v) => v.trim().to_string(),
+        None => response,
+    }
+}
+
+async fn playtime(session: &Session, player: &str) -> String {
+    let now = unix_millis();
+    let playtime = PLAYERS.lock().await.playtime(player, now);
+    let servers = match playtime.as_object() {
+        Some(v) if !v.is_empty() => v,
+        _ => return format!("{player} has not played here"),
+    };
+    let total: u64 = servers
+        .values()
+        .filter_map(|v| v["playtime"].as_u64())
+        .sum();
+    match servers
+        .get(&session.name)
+        .and_then(|v| v["playtime"].as_u64())
+    {
+        Some(v) if servers.len() == 1 => format!("{player} has played for {}", duration(v)),
+        Some(v) => format!(
+            "{player} has played for {} here, {} on all servers",
+            duration(v),
+            duration(total)
+        ),
+        None => format!(
+            "{player} has played for {} on other servers",
+            duration(total)
+        ),
+    }
+}
+
+async fn seen(player: &str) -> String {
+    let seen = match PLAYERS.lock().await.last_seen(player) {
+        Some(v) => v,
+        None => return format!("{player} has never been seen"),
+    };
+    let name = seen["player"].as_str().unwrap_or(player);
+    let server = seen["session"].as_str().unwrap_or_default();
+    if seen["online"].as_bool() == Some(true) {
+        return format!("{name} is online on {server}");
+    }
+    let ago = unix_millis().saturating_sub(seen["last_seen"].as_u64().unwrap_or_default());
+    format!("{name} was last seen on {server} {} ago", duration(ago))
+}
+
+// paper and forks have a tps command, vanilla only reports tick times since 1.20.3
+async fn tps(session: &Session) -> String {
+    let rcon = match &session.rcon {
+        Some(v) => v,
+        None => return "tps needs rcon to be set up".to_string(),
+    };
+    for cmd in ["tps", "tick query"] {
+        if let Ok(v) = rcon.rcon_send_with_response(cmd).await {
+            let v = FORMATTING.replace_all(&v, "");
+            if !v.trim().is_empty() && !v.starts_with("Unknown") {
+                return v.trim().to_string();
+            }
+        }
+    }
+    "could not get the tps of this server".to_string()
+}
+
+fn help(session: &Session, player: &str) -> String {
+    let game = match &session.game {
+        Some(v) => v,
+        None => return String::new(),
+    };
+    let prefix = game.cmd_prefix.as_deref().unwrap_or(DEFAULT_PREFIX);
+    let mut names: Vec<String> = BUILTIN.iter().map(|v| v.to_string()).collect();
+    if let Some(v) = &game.commands {
+        names.extend(
+            v.keys()
+                .filter(|k| !names.contains(k))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+    }
+    let names: Vec<String> = names
+        .into_iter()
+        .filter(|n| {
+            let rule = game
+                .commands
+                .as_ref()
+                .and_then(|c| c.get(n))
+                .cloned()
+                .unwrap_or_default();
+            rule.enabled != Some(false)
+                && (BUILTIN.contains(&n.as_str()) || rule.reply.is_some() || rule.ws.is_some())
+                && allowed(session, player, &rule.permission(n))
+        })
+        .map(|n| format!("{prefix}{n}"))
+        .collect();
+    format!("commands: {}", names.join(", "))
+}
+
+fn allowed(session: &Session, player: &str, permission: &[String]) -> bool {
+    permission.iter().any(|p| match p.as_str() {
+        EVERYONE => true,
+        OPS => ops(session).iter().any(|o| o.eq_ignore_ascii_case(player)),
+        name => name.eq_ignore_ascii_case(player),
+    })
+}
+
+// names in the ops.json next to the world folder
+fn ops(session: &Session) -> Vec<String> {
+    let path = match session
+        .game
+        .as_ref()
+        .and_then(|g| g.server_file("ops.json"))
+    {
+        Some(v) => v,
+        None => return Vec::new(),
+    };
+    let data = match fs::read_to_string(&path) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+    match serde_json::from_str::<Vec<serde_json::Value>>(&data) {
+        Ok(v) => v
+            .iter()
+            .filter_map(|o| o["name"].as_str().map(|n| n.to_string()))
+            .collect(),
+        Err(e) => {
+            warn!("could not read {}: {e}", path.display());
+            Vec::new()
+        }
+    }
+}
+
+async fn reply(session: &Session, player: &str, text: &str) {
+    let message = Component::from_markdown(text).color("gray").tellraw(player);
+    match &session.rcon {
+        Some(v) => {
+            let _ = v.rcon_send(&message).await;
+        }
+        None => Session::send_command(&session.name, &message),
+    }
+}
+
+fn duration(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => format!("{}s", ms / 1000),
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h {m}m"),
+    }
+}
@@ -0,0 +1,349 @@
+use serde_derive::Serialize;
+
+// named colors that are readable on the default chat background, used for session prefixes
+const PREFIX_COLORS: [&str; 8] = [
+    "aqua",
+    "gold",
+    "green",
+    "light_purple",
+    "yellow",
+    "blue",
+    "red",
+    "dark_aqua",
+];
+
+// minecraft json text component, serialized straight into tellraw so nothing has to be escaped
+// by hand
+#[derive(Serialize, Clone, Default, Debug)]
+pub(crate) struct Component {
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bold: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    italic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    underlined: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strikethrough: Option<bool>,
+    #[serde(rename = "clickEvent", skip_serializing_if = "Option::is_none")]
+    click_event: Option<ClickEvent>,
+    #[serde(rename = "hoverEvent", skip_serializing_if = "Option::is_none")]
+    hover_event: Option<HoverEvent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    extra: Vec<Component>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct ClickEvent {
+    action: &'static str,
+    value: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct HoverEvent {
+    action: &'static str,
+    contents: Box<Component>,
+}
+
+// styles that can be toggled on and off while reading markdown
+#[derive(Clone, Copy, Default, PartialEq)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+}
+
+impl Component {
+    pub(crate) fn text<T: Into<String>>(text: T) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn color<T: Into<String>>(mut self, color: T) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    pub(crate) fn bold(mut self) -> Self {
+        self.bold = Some(true);
+        self
+    }
+
+    pub(crate) fn italic(mut self) -> Self {
+        self.italic = Some(true);
+        self
+    }
+
+    pub(crate) fn underlined(mut self) -> Self {
+        self.underlined = Some(true);
+        self
+    }
+
+    pub(crate) fn strikethrough(mut self) -> Self {
+        self.strikethrough = Some(true);
+        self
+    }
+
+    pub(crate) fn hover(mut self, contents: Component) -> Self {
+        self.hover_event = Some(HoverEvent {
+            action: "show_text",
+            contents: Box::new(contents),
+        });
+        self
+    }
+
+    pub(crate) fn url<T: Into<String>>(mut self, url: T) -> Self {
+        self.click_event = Some(ClickEvent {
+            action: "open_url",
+            value: url.into(),
+        });
+        self
+    }
+
+    pub(crate) fn append(mut self, component: Component) -> Self {
+        self.extra.push(component);
+        self
+    }
+
+    // a clickable link, the url is shown when hovering over it
+    pub(crate) fn link<T: Into<String>>(text: T, url: &str) -> Self {
+        Self::text(text)
+            .color("aqua")
+            .underlined()
+            .url(url)
+            .hover(Self::text(url))
+    }
+
+    // `[session]` prefix with a color that stays the same for each session unless a route
+    // overrides either of them
+    pub(crate) fn prefix(origin: &str, label: Option<&str>, color: Option<&str>) -> Self {
+        let color = color.unwrap_or(
+            PREFIX_COLORS[origin.bytes().map(|b| b as usize).sum::<usize>() % PREFIX_COLORS.len()],
+        );
+        let label = match label {
+            Some(v) => v.to_string(),
+            None => format!("[{origin}]"),
+        };
+        Self::text(label)
+            .color(color)
+            .hover(Self::text(format!("sent from {origin}")))
+    }
+
+    // convert discord style markdown, supports **bold**, *italic*, _italic_, __underline__,
+    // ~~strikethrough~~, [text](url) and bare links
+    pub(crate) fn from_markdown(text: &str) -> Self {
+        let mut root = Self::text("");
+        let mut style = Style::default();
+        let mut buffer = String::new();
+        let mut rest = text;
+        let mut prev: Option<char> = None;
+        while let Some(c) = rest.chars().next() {
+            // escaped characters are always literal
+            if c == '\\' {
+                let mut chars = rest[1..].chars();
+                match chars.next() {
+                    Some(n) => {
+                        buffer.push(n);
+                        prev = Some(n);
+                        rest = chars.as_str();
+                    }
+                    None => {
+                        buffer.push(c);
+                        rest = "";
+                    }
+                }
+                continue;
+            }
+            let in_word = prev.is_some_and(char::is_alphanumeric);
+            if let Some((marker, len)) = Self::marker(rest, &style, in_word) {
+                let mut toggled = style;
+                match marker {
+                    "**" => toggled.bold = !toggled.bold,
+                    "__" => toggled.underlined = !toggled.underlined,
+                    "~~" => toggled.strikethrough = !toggled.strikethrough,
+                    _ => toggled.italic = !toggled.italic,
+                }
+                root = root.append_styled(&mut buffer, style);
+                style = toggled;
+                prev = None;
+                rest = &rest[len..];
+                continue;
+            }
+            if let Some((label, url, len)) = Self::find_link(rest) {
+                root = root.append_styled(&mut buffer, style);
+                root = root.append(Self::styled(Self::link(label, url), style));
+                prev = None;
+                rest = &rest[len..];
+                continue;
+            }
+            buffer.push(c);
+            prev = Some(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        root.append_styled(&mut buffer, style)
+    }
+
+    // the marker at the start of the text if it opens a style with a matching close, or closes
+    // an open one, underscores inside of words are left alone like in discord
+    fn marker<'a>(text: &'a str, style: &Style, in_word: bool) -> Option<(&'a str, usize)> {
+        for (marker, active) in [
+            ("**", style.bold),
+            ("__", style.underlined),
+            ("~~", style.strikethrough),
+            ("*", style.italic),
+            ("_", style.italic),
+        ] {
+            if !text.starts_with(marker) {
+                continue;
+            }
+            let len = marker.len();
+            let inner = &text[len..];
+            if !active && in_word && marker.starts_with('_') {
+                continue;
+            }
+            // opening needs something to style before the closing marker
+            let closes = match inner.chars().next() {
+                Some(c) => !inner.starts_with(marker) && inner[c.len_utf8()..].contains(marker),
+                None => false,
+            };
+            if active || closes {
+                return Some((&text[..len], len));
+            }
+        }
+        None
+    }
+
+    // a `[label](url)` or a bare url at the start of the text, returns the label, url and length
+    // of text consumed
+    fn find_link(text: &str) -> Option<(&str, &str, usize)> {
+        if text.starts_with("http://") || text.starts_with("https://") {
+            let len = text.find(char::is_whitespace).unwrap_or(text.len());
+            return Some((&text[..len], &text[..len], len));
+        }
+        let label_end = text.strip_prefix('[')?.find("](")? + 1;
+        let url_end = label_end + 2 + text[label_end + 2..].find(')')?;
+        let url = &text[label_end + 2..url_end];
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return None;
+        }
+        Some((&text[1..label_end], url, url_end + 1))
+    }
+
+    fn styled(mut component: Self, style: Style) -> Self {
+        if style.bold {
+            component = component.bold();
+        }
+        if style.italic {
+            component = component.italic();
+        }
+        if style.underlined {
+            component = component.underlined();
+        }
+        if style.strikethrough {
+            component = component.strikethrough();
+        }
+        component
+    }
+
+    // move the buffered text into a child with the given style
+    fn append_styled(self, buffer: &mut String, style: Style) -> Self {
+        if buffer.is_empty() {
+            return self;
+        }
+        let text = std::mem::take(buffer);
+        self.append(Self::styled(Self::text(text), style))
+    }
+
+    pub(crate) fn tellraw(&self, target: &str) -> String {
+        format!(
+            "tellraw {target} {}",
+            serde_json::to_string(self).unwrap_or_default()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn markdown(text: &str) -> Value {
+        serde_json::to_value(Component::from_markdown(text)).unwrap()["extra"].clone()
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            markdown("**bold *both* bold**"),
+            json!([
+                { "text": "bold ", "bold": true },
+                { "text": "both", "bold": true, "italic": true },
+                { "text": " bold", "bold": true },
+            ])
+        );
+        assert_eq!(
+            markdown("__*both*__"),
+            json!([{ "text": "both", "italic": true, "underlined": true }])
+        );
+        // overlapping markers close in the order they come
+        assert_eq!(
+            markdown("*a **b* c**"),
+            json!([
+                { "text": "a ", "italic": true },
+                { "text": "b", "bold": true, "italic": true },
+                { "text": " c", "bold": true },
+            ])
+        );
+    }
+
+    #[test]
+    fn unclosed() {
+        assert_eq!(markdown("**not bold"), json!([{ "text": "**not bold" }]));
+        assert_eq!(markdown("***"), json!([{ "text": "***" }]));
+        assert_eq!(
+            markdown("~~gone~~ ~~kept"),
+            json!([
+                { "text": "gone", "strikethrough": true },
+                { "text": " ~~kept" },
+            ])
+        );
+    }
+
+    #[test]
+    fn literal() {
+        assert_eq!(
+            markdown(r"\*not italic\*"),
+            json!([{ "text": "*not italic*" }])
+        );
+        assert_eq!(
+            markdown(r"\*\*not bold\*\*"),
+            json!([{ "text": "**not bold**" }])
+        );
+        // a trailing backslash has nothing to escape
+        assert_eq!(markdown("end\\"), json!([{ "text": "end\\" }]));
+        // underscores inside words are part of the name
+        assert_eq!(
+            markdown("snake_case_name"),
+            json!([{ "text": "snake_case_name" }])
+        );
+    }
+
+    #[test]
+    fn links() {
+        let parts = markdown("[site](https://a.example) or https://b.example");
+        assert_eq!(parts[0]["text"], "site");
+        assert_eq!(parts[0]["clickEvent"]["value"], "https://a.example");
+        assert_eq!(parts[1], json!({ "text": " or " }));
+        assert_eq!(parts[2]["clickEvent"]["value"], "https://b.example");
+        // only web links are clickable
+        assert_eq!(
+            markdown("[x](file:///etc/passwd)"),
+            json!([{ "text": "[x](file:///etc/passwd)" }])
+        );
+    }
+}
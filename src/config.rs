@@ -1,16 +1,21 @@
-use crate::ws::SESSIONS;
+use crate::init;
+use crate::rcon::{self, DEFAULT_RCON_TIMEOUT};
+use crate::routing::{Route, Router};
+use crate::validate::{self, ConfigError, CONFIG_FILES};
+use crate::ws::{DATA_DIR, SESSIONS};
 use crate::{bridge::Session, exit};
 use log::error;
 use log::info;
-use rcon_rs::Client;
 use serde_derive::{Deserialize, Serialize};
-use serde_json::from_str;
-use std::path::PathBuf;
-use std::{fs, fs::File};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
 use tokio::process::Command;
+use uuid::Uuid;
 
 // main config
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub(crate) struct Config {
     pub ws_ip: String,
     pub ws_port: u64,
@@ -21,11 +26,28 @@ pub(crate) struct Config {
     pub scripts: Option<Vec<Script>>,
     pub restart_script: Option<String>,
     pub recompile_directory: Option<String>,
+    // groups of sessions that share chat with each other
+    pub channels: Option<HashMap<String, Vec<String>>>,
+    pub routes: Option<Vec<Route>>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub router: Router,
+    // amount of events kept per session for the HISTORY command
+    pub history_size: Option<usize>,
+    // amount of events per session sent to clients once they authenticate
+    pub history_replay: Option<usize>,
+    // seconds the player list of each session is reused for
+    pub list_cache_ttl: Option<u64>,
+    // address download links point to, defaults to http://ws_ip:ws_port
+    pub public_url: Option<String>,
+    // seconds download links and exported files last
+    pub download_ttl: Option<u64>,
+    // key download links are signed with, random on every start if not set
+    pub download_secret: Option<String>,
 }
 
 // for any optional scripts, if either interval or absolute is 0 then we will use the non zero
 // file, otherwise the script never gets executed.
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub(crate) struct Script {
     pub description: String,
     pub interval: Option<u64>,
@@ -64,136 +86,125 @@ pub(crate) struct Rcon {
     pub ip: Option<String>,
     pub port: u16,
     pub password: String,
+    // ms to wait for connecting or a response before giving up
+    pub timeout: Option<u64>,
 }
 
 impl Rcon {
+    pub(crate) fn address(&self) -> String {
+        format!(
+            "{}:{}",
+            self.ip.as_deref().unwrap_or("localhost"),
+            self.port
+        )
+    }
+
     pub(crate) async fn rcon_send(&self, msg: &str) -> Result<(), std::io::Error> {
-        let mut conn = self.connect().await?;
-        if conn.auth(&self.password).is_ok() {
-            let _ = conn.send(msg, None);
-        };
-        Ok(())
+        self.rcon_send_with_response(msg).await.map(|_| ())
     }
+
     pub(crate) async fn rcon_send_with_response(
         &self,
         msg: &str,
-    ) -> Result<Option<String>, std::io::Error> {
-        let mut conn = self.connect().await?;
-        if conn.auth(&self.password).is_ok() {
-            return match conn.send(msg, None) {
-                Ok(v) => Ok(Some(v)),
-                Err(_) => Ok(None),
-            };
-        };
-        Ok(None)
-    }
-
-    async fn connect(&self) -> Result<Client, std::io::Error> {
-        Client::new(
-            &self.ip.clone().unwrap_or_else(|| "localhost".to_string()),
-            &self.port.to_string(),
+    ) -> Result<String, std::io::Error> {
+        rcon::exec(
+            &self.address(),
+            &self.password,
+            msg,
+            Duration::from_millis(self.timeout.unwrap_or(DEFAULT_RCON_TIMEOUT)),
         )
+        .await
     }
 }
 
 impl Config {
-    pub(crate) fn load_config<T>(path: T) -> Self
-    where
-        T: AsRef<str> + std::fmt::Display,
-    {
-        let path = path.to_string();
-        let config_path = &(path.to_owned() + "/config.json");
-        if !PathBuf::from(config_path).exists() {
-            Config::default(&path);
-            Config::default_root_cfg(path.to_owned());
+    // config.toml or config.json in the config folder, every problem with it is logged before
+    // exiting
+    pub(crate) fn load_config(path: &Path) -> Self {
+        if validate::config_file(path).is_none() {
+            eprintln!(
+                "*info: generating default config in {}, `taurus init` can set it up instead",
+                path.display()
+            );
+            Config::default(path);
+            Config::default_root_cfg(path);
         }
-
-        let data = match fs::read_to_string(path.to_owned() + "/config.json") {
-            Ok(t) => t,
-            Err(_) => {
-                error!("no config file found at {}!", path);
-                eprintln!("*info: generating default config");
-                Config::default_root_cfg(path.to_owned());
-                if !PathBuf::from(config_path).exists() {
-                    error!("could not read just generated config, exiting");
-                    exit!();
+        match Self::try_load_config(path) {
+            Ok(v) => v,
+            Err(errors) => {
+                for e in errors {
+                    error!("{e}");
                 }
-                fs::read_to_string(path + "/config.json").unwrap()
-            }
-        };
-
-        let conf: Self = match from_str(&data) {
-            Ok(t) => t,
-            Err(_) => {
                 error!("invalid config file! exiting");
                 exit!();
             }
-        };
+        }
+    }
+
+    pub(crate) fn try_load_config(path: &Path) -> Result<Self, Vec<ConfigError>> {
+        let loaded = validate::load_config(path).map_err(|e| vec![e])?;
+        let errors = validate::check(Some(&loaded), None);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut conf = loaded.value;
+        conf.router = Router::new(
+            conf.channels.as_ref(),
+            conf.routes.as_deref().unwrap_or_default(),
+        );
         if conf.scripts.is_some() {
             for i in conf.scripts.as_ref().unwrap() {
                 info!("*info: found script: {}", i.description);
             }
         }
-        conf
+        Ok(conf)
     }
 
-    fn default_root_cfg(path: String) {
-        File::create(path.to_owned() + "/config.json").unwrap();
-
-        let default = r#"{
-    "ws_ip": "127.0.0.1",
-    "ws_port": "7500",
-    "backup_location": "",
-    "scripts": [
-        {
-            "description": "very cool script",
-            "interval": 0,
-            "absolute": 0,
-            "shell_cmd": "",
-            "mc_cmd": ""
+    fn default_root_cfg(path: &Path) {
+        let backups = DATA_DIR.join("backups");
+        let _ = fs::create_dir_all(&backups);
+        let default = init::config_text(
+            "127.0.0.1",
+            7500,
+            &Uuid::new_v4().to_simple().to_string(),
+            &backups.display().to_string(),
+        );
+        if let Err(e) = fs::write(path.join(CONFIG_FILES[0]), default) {
+            error!("could not write default config: {e}");
         }
-    ],
-    "restart_script": "",
-    "recompile_directory": ""
-}"#;
-
-        fs::write(path + "/config.json", default).unwrap();
     }
 
-    pub(crate) fn load_sessions(path: String) -> Vec<Session> {
-        if !PathBuf::from(&(path.to_owned() + "/servers/")).exists() {
-            Self::default(&path);
+    pub(crate) fn load_sessions(path: &Path) -> Vec<Session> {
+        if !path.join("servers").exists() {
+            Self::default(path);
         }
-
-        let mut sessions = Vec::new();
-
-        for i in fs::read_dir(path + "servers/")
-            .expect("*error: \x1b[31mfailed to read server directory\x1b[0m")
-        {
-            let i = i.unwrap();
-            info!("*info: reading: {:#?}", i.path().display().to_string());
-
-            let data = match fs::read_to_string(i.path()) {
-                Ok(t) => t,
-                Err(_) => continue,
-            };
-
-            match from_str(&data) {
-                Ok(t) => sessions.push(t),
-                Err(e) => {
-                    error!("{:?}", e);
-                    error!("invalid server config! exiting");
-                    exit!();
+        match Self::try_load_sessions(path) {
+            Ok(v) => v,
+            Err(errors) => {
+                for e in errors {
+                    error!("{e}");
                 }
-            };
+                error!("invalid server config! exiting");
+                exit!();
+            }
+        }
+    }
+
+    pub(crate) fn try_load_sessions(path: &Path) -> Result<Vec<Session>, Vec<ConfigError>> {
+        let (sessions, mut errors) = validate::load_sessions(path);
+        errors.extend(validate::check(None, Some(&sessions)));
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        for session in &sessions {
+            info!("*info: read: {}", session.file.display());
         }
-        sessions
+        Ok(sessions.into_iter().map(|s| s.value).collect())
     }
 
-    fn default(path: &str) {
-        let current_directory = PathBuf::from(path);
-        let _ = fs::create_dir(current_directory.join("servers"));
-        let _ = fs::File::create(current_directory.join("./server/servers.json"));
-        let _ = fs::File::create(current_directory.join("scripts.json"));
+    fn default(path: &Path) {
+        if let Err(e) = fs::create_dir_all(path.join("servers")) {
+            error!("could not create {}: {e}", path.join("servers").display());
+        }
     }
 }
@@ -0,0 +1,248 @@
+use crate::{validate, ws::CONFIG_DIR};
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::{fs, time::Duration};
+use tokio::{net::TcpStream, time::timeout};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// seconds to wait for a response before assuming the password or command was wrong, taurus
+// doesn't answer either of those
+const DEFAULT_TIMEOUT: u64 = 10;
+// commands that never get a response
+const NO_RESPONSE: [&str; 4] = ["MSG", "URL", "CMD", "SHELL"];
+
+struct Options {
+    url: Option<String>,
+    password_file: Option<String>,
+    timeout: u64,
+    // wait for jobs started by the command and print their result
+    wait: bool,
+    // print responses as they are sent
+    raw: bool,
+    command: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            url: None,
+            password_file: None,
+            timeout: DEFAULT_TIMEOUT,
+            wait: false,
+            raw: false,
+            command: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{arg} needs a value"))
+            };
+            match arg.as_str() {
+                "--url" => options.url = Some(value()?),
+                "--password-file" => options.password_file = Some(value()?),
+                "--timeout" => {
+                    options.timeout = value()?
+                        .parse()
+                        .map_err(|_| "--timeout takes seconds".to_owned())?
+                }
+                "--wait" => options.wait = true,
+                "--raw" => options.raw = true,
+                // everything from the command on is passed along as it is
+                _ => {
+                    options.command.push(arg.clone());
+                    options.command.extend(args.cloned());
+                    break;
+                }
+            }
+        }
+        if options.command.is_empty() {
+            return Err("no command given".to_owned());
+        }
+        Ok(options)
+    }
+}
+
+fn fail<T: std::fmt::Display>(message: T) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
+
+// the address and password of the local daemon, taken from its config so overrides and secret
+// files apply the same way
+fn local(options: &Options) -> (String, String) {
+    let password = options.password_file.as_ref().map(|f| {
+        fs::read_to_string(f)
+            .map(|p| p.trim_end_matches(['\r', '\n']).to_owned())
+            .unwrap_or_else(|e| fail(format!("unable to read {f}: {e}")))
+    });
+    if let (Some(url), Some(password)) = (&options.url, &password) {
+        return (url.clone(), password.clone());
+    }
+    let config = match validate::load_config(&CONFIG_DIR) {
+        Ok(v) => v.value,
+        Err(e) => fail(format!(
+            "{e}\nuse --url and --password-file to connect without it"
+        )),
+    };
+    // listening on every interface includes localhost
+    let ip = match config.ws_ip.as_str() {
+        "0.0.0.0" => "127.0.0.1",
+        v => v,
+    };
+    let url = options
+        .url
+        .clone()
+        .unwrap_or_else(|| format!("ws://{ip}:{}/taurus", config.ws_port));
+    (url, password.unwrap_or(config.ws_password))
+}
+
+// `backup smp` is sent as `BACKUP smp`, `list-backups` as `LIST_BACKUPS`
+fn command(words: &[String]) -> String {
+    let name = words[0].to_uppercase().replace('-', "_");
+    match words.len() {
+        1 => name,
+        _ => format!("{name} {}", words[1..].join(" ")),
+    }
+}
+
+// the next response to `name`, events and chat sent in the meantime are skipped. None if
+// nothing came within the timeout
+async fn response(socket: &mut Socket, seconds: u64, name: &str) -> Option<String> {
+    let wait = async {
+        while let Some(message) = socket.next().await {
+            match message {
+                Ok(Message::Text(text)) if text.split(' ').next() == Some(name) => {
+                    return Some(text)
+                }
+                Ok(Message::Close(_)) | Err(_) => return None,
+                _ => {}
+            }
+        }
+        None
+    };
+    match timeout(Duration::from_secs(seconds), wait).await {
+        Ok(Some(v)) => Some(v),
+        Ok(None) => fail("taurus closed the connection"),
+        Err(_) => None,
+    }
+}
+
+// taurus doesn't answer a password, and nothing else until it was the right one
+async fn login(socket: &mut Socket, password: String, seconds: u64) {
+    send(socket, password).await;
+    send(socket, "PING".to_owned()).await;
+    if response(socket, seconds, "PONG").await.is_none() {
+        fail("authentication failed, check the password");
+    }
+}
+
+async fn send(socket: &mut Socket, text: String) {
+    if let Err(e) = socket.send(Message::Text(text)).await {
+        fail(format!("unable to send to taurus: {e}"));
+    }
+}
+
+// responses start with the command they answer, the rest is shown as json if it is json
+fn print(response: &str, raw: bool) {
+    if raw {
+        println!("{response}");
+        return;
+    }
+    let body = response.split_once(' ').map_or("", |(_, b)| b);
+    match serde_json::from_str::<Value>(body) {
+        Ok(v @ (Value::Object(_) | Value::Array(_))) => {
+            println!("{}", serde_json::to_string_pretty(&v).unwrap_or_default())
+        }
+        _ => println!("{body}"),
+    }
+}
+
+// errors are sent back as text such as `Invalid Session Target` or `failed to ...`
+fn is_error(response: &str) -> bool {
+    let body = response
+        .split_once(' ')
+        .map_or("", |(_, b)| b)
+        .to_lowercase();
+    ["invalid", "failed", "unable", "no "]
+        .iter()
+        .any(|p| body.starts_with(p))
+        || body.ends_with("not found")
+}
+
+// ask for the job until it's done, its result replaces the `started job` response
+async fn wait_for_job(socket: &mut Socket, id: &str, options: &Options) -> String {
+    loop {
+        send(socket, format!("JOB {id}")).await;
+        let job = match response(socket, options.timeout, "JOB").await {
+            Some(v) => v,
+            None => fail(format!(
+                "no response for job {id} within {}s",
+                options.timeout
+            )),
+        };
+        let state = job
+            .strip_prefix("JOB ")
+            .and_then(|j| serde_json::from_str::<Value>(j).ok())
+            .and_then(|j| j["state"].as_str().map(|s| s.to_owned()));
+        match state.as_deref() {
+            Some("running") => tokio::time::sleep(Duration::from_secs(1)).await,
+            Some("failed") => {
+                print(&job, options.raw);
+                std::process::exit(1);
+            }
+            _ => return job,
+        }
+    }
+}
+
+// `taurus ctl <COMMAND ...>`, runs a websocket command on the running daemon and prints the
+// response
+pub(crate) async fn run(args: &[String]) {
+    let options = Options::parse(args).unwrap_or_else(|e| {
+        fail(format!(
+            "{e}\nusage: taurus ctl [--url <ws url>] [--password-file <file>] [--timeout <seconds>] [--wait] [--raw] <COMMAND> [ARGS...]"
+        ))
+    });
+    let (url, password) = local(&options);
+    let mut socket = match connect_async(url.as_str()).await {
+        Ok((v, _)) => v,
+        Err(e) => fail(format!("unable to connect to {url}: {e}")),
+    };
+    let command = command(&options.command);
+    login(&mut socket, password, options.timeout).await;
+    send(&mut socket, command.clone()).await;
+    let name = command.split(' ').next().unwrap_or_default();
+    if NO_RESPONSE.contains(&name) {
+        let _ = socket.close(None).await;
+        return;
+    }
+    // PING is answered with PONG
+    let reply = match name {
+        "PING" => "PONG",
+        v => v,
+    };
+    let mut response = match response(&mut socket, options.timeout, reply).await {
+        Some(v) => v,
+        None => fail(format!(
+            "no response within {}s, check the command's arguments",
+            options.timeout
+        )),
+    };
+    if options.wait {
+        if let Some(id) = response
+            .split_once(" started job ")
+            .map(|(_, id)| id.trim().to_owned())
+        {
+            response = wait_for_job(&mut socket, &id, &options).await;
+        }
+    }
+    let _ = socket.close(None).await;
+    print(&response, options.raw);
+    if is_error(&response) {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::utils::unix_millis;
+use serde_derive::{Deserialize, Serialize};
+use std::{fmt, sync::Mutex};
+
+lazy_static::lazy_static! {
+    // events that don't come from a session's output, sent along with the next bridge update
+    static ref PENDING: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+}
+
+// queue an event from outside the bridge, e.g. a finished background job
+pub(crate) fn emit(event: Event) {
+    PENDING.lock().unwrap().push(event);
+}
+
+pub(crate) fn take_pending() -> Vec<Event> {
+    std::mem::take(&mut *PENDING.lock().unwrap())
+}
+
+// an event from a session, sent to websocket clients as `EVENT {json}`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct Event {
+    pub session: String,
+    // unix timestamp in ms
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+impl Event {
+    pub(crate) fn new(session: &str, kind: EventKind) -> Self {
+        Self {
+            session: session.to_string(),
+            timestamp: unix_millis(),
+            kind,
+        }
+    }
+
+    pub(crate) fn to_message(&self) -> String {
+        format!("EVENT {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+// things that can happen on a server, produced by a session's log parser
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum EventKind {
+    Chat {
+        player: String,
+        message: String,
+    },
+    PlayerJoin {
+        player: String,
+    },
+    PlayerLeave {
+        player: String,
+    },
+    Death {
+        player: String,
+        cause: String,
+    },
+    Advancement {
+        player: String,
+        advancement: String,
+    },
+    ServerStarting {
+        version: String,
+    },
+    ServerStarted {
+        seconds: f64,
+    },
+    ServerStopping,
+    Lag {
+        ms: u64,
+        ticks: u64,
+    },
+    JobFinished {
+        id: u64,
+        job: String,
+        ok: bool,
+    },
+    // sessions by name and the top level config fields that changed
+    ConfigReloaded {
+        added: Vec<String>,
+        removed: Vec<String>,
+        changed: Vec<String>,
+        config: Vec<String>,
+    },
+}
+
+impl EventKind {
+    // whether the event should be relayed as a chat line to the bridge
+    pub(crate) fn is_chat_line(&self) -> bool {
+        matches!(
+            self,
+            Self::Chat { .. }
+                | Self::PlayerJoin { .. }
+                | Self::PlayerLeave { .. }
+                | Self::Death { .. }
+                | Self::Advancement { .. }
+        )
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chat { player, message } => write!(f, "<{player}> {message}"),
+            Self::PlayerJoin { player } => write!(f, "{player} joined the game"),
+            Self::PlayerLeave { player } => write!(f, "{player} left the game"),
+            Self::Death { player, cause } => write!(f, "{player} {cause}"),
+            Self::Advancement {
+                player,
+                advancement,
+            } => write!(f, "{player} has made the advancement [{advancement}]"),
+            Self::ServerStarting { version } => {
+                write!(f, "Starting minecraft server version {version}")
+            }
+            Self::ServerStarted { seconds } => write!(f, "Done ({seconds:.3}s)!"),
+            Self::ServerStopping => write!(f, "Stopping the server"),
+            Self::Lag { ms, ticks } => write!(
+                f,
+                "Can't keep up! Is the server overloaded? Running {ms}ms or {ticks} ticks behind"
+            ),
+            Self::JobFinished { id, job, ok } => match ok {
+                true => write!(f, "Job {id} ({job}) finished"),
+                false => write!(f, "Job {id} ({job}) failed"),
+            },
+            Self::ConfigReloaded {
+                added,
+                removed,
+                changed,
+                config,
+            } => write!(
+                f,
+                "Config reloaded, {} sessions added, {} removed, {} changed and {} config fields changed",
+                added.len(),
+                removed.len(),
+                changed.len(),
+                config.len()
+            ),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    utils::unix_millis,
+    ws::{CONFIG, EXPORTS_PATH},
+};
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_derive::Deserialize;
+use sha2::Sha256;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+use warp::{
+    http::StatusCode,
+    path::Peek,
+    reject::{self, Reject},
+    reply, Filter, Rejection, Reply,
+};
+
+// seconds a download link and the exported file behind it last
+pub(crate) const DEFAULT_DOWNLOAD_TTL: u64 = 3600;
+// everything but these is escaped in links, `/` keeps separating folders
+const PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+lazy_static::lazy_static! {
+    // used when no download_secret is configured, links then stop working on restart
+    static ref RANDOM_SECRET: Vec<u8> = [Uuid::new_v4(), Uuid::new_v4()]
+        .iter()
+        .flat_map(|u| u.as_bytes().to_vec())
+        .collect();
+}
+
+#[derive(Deserialize)]
+struct Signature {
+    expires: u64,
+    signature: String,
+}
+
+#[derive(Debug)]
+struct InvalidLink;
+
+impl Reject for InvalidLink {}
+
+async fn secret() -> Vec<u8> {
+    match &CONFIG.read().await.download_secret {
+        Some(v) => v.as_bytes().to_vec(),
+        None => RANDOM_SECRET.clone(),
+    }
+}
+
+// `kind` is export or backup, `path` the file relative to that folder as it is on disk, links
+// carry it escaped
+fn mac(secret: &[u8], kind: &str, path: &str, expires: u64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac takes keys of any size");
+    mac.update(format!("{kind}/{path}:{expires}").as_bytes());
+    mac
+}
+
+fn sign(secret: &[u8], kind: &str, path: &str, expires: u64) -> String {
+    hex::encode(mac(secret, kind, path, expires).finalize().into_bytes())
+}
+
+fn verify(secret: &[u8], kind: &str, path: &str, query: &Signature) -> bool {
+    if query.expires < unix_millis() / 1000 {
+        return false;
+    }
+    let signature = match hex::decode(&query.signature) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    mac(secret, kind, path, query.expires)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+fn encode(path: &str) -> String {
+    utf8_percent_encode(path, PATH).to_string()
+}
+
+// the path of a request as it is on disk, None if it doesn't decode to utf-8
+fn decode(path: &str) -> Option<String> {
+    percent_decode_str(path)
+        .decode_utf8()
+        .ok()
+        .map(|p| p.into_owned())
+}
+
+// a link to a file served by taurus itself that stops working after download_ttl seconds
+pub(crate) async fn signed_url(kind: &str, path: &str) -> String {
+    let (base, ttl) = {
+        let config = CONFIG.read().await;
+        let base = match &config.public_url {
+            Some(v) => v.trim_end_matches('/').to_owned(),
+            None => format!("http://{}:{}", config.ws_ip, config.ws_port),
+        };
+        (base, config.download_ttl.unwrap_or(DEFAULT_DOWNLOAD_TTL))
+    };
+    let expires = unix_millis() / 1000 + ttl;
+    let signature = sign(&secret().await, kind, path, expires);
+    format!(
+        "{base}/taurus/files/{kind}/{}?expires={expires}&signature={signature}",
+        encode(path)
+    )
+}
+
+// copy a file out of a world so it can be downloaded, each export gets its own folder so names
+// can't be guessed or collide
+pub(crate) async fn export(source: &Path) -> Result<String, String> {
+    let name = match source.file_name() {
+        Some(v) => v.to_string_lossy().to_string(),
+        None => return Err("Invalid file".to_owned()),
+    };
+    let id = Uuid::new_v4().to_simple().to_string();
+    let folder = EXPORTS_PATH.join(&id);
+    tokio::fs::create_dir_all(&folder)
+        .await
+        .map_err(|_| "Unable to create export folder".to_owned())?;
+    tokio::fs::copy(source, folder.join(&name))
+        .await
+        .map_err(|_| "Failed to copy file into export folder".to_owned())?;
+    Ok(signed_url("export", &format!("{id}/{name}")).await)
+}
+
+// only lets a request through to the files if its link is signed and hasn't expired
+fn signed(kind: &'static str) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path::peek()
+        .and(warp::query::<Signature>())
+        .and_then(move |path: Peek, query: Signature| async move {
+            // warp leaves the path escaped the way it was in the link
+            let valid = match decode(path.as_str()) {
+                Some(path) => verify(&secret().await, kind, &path, &query),
+                None => false,
+            };
+            match valid {
+                true => Ok(()),
+                false => Err(reject::custom(InvalidLink)),
+            }
+        })
+        .untuple_one()
+}
+
+async fn invalid_link(err: Rejection) -> Result<reply::Response, Rejection> {
+    match err.find::<InvalidLink>() {
+        Some(_) => Ok(
+            reply::with_status("Invalid or expired link", StatusCode::FORBIDDEN).into_response(),
+        ),
+        None => Err(err),
+    }
+}
+
+// GET /taurus/files/<export/backup>/<path>, range requests are handled by warp::fs
+pub(crate) fn routes(
+    backup_location: PathBuf,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    let exports = warp::path!("taurus" / "files" / "export" / ..)
+        .and(signed("export"))
+        .and(warp::fs::dir(EXPORTS_PATH.to_path_buf()))
+        .map(|file: warp::fs::File| file.into_response());
+    let backups = warp::path!("taurus" / "files" / "backup" / ..)
+        .and(signed("backup"))
+        .and(warp::fs::dir(backup_location))
+        .map(|file: warp::fs::File| file.into_response());
+    exports.or(backups).unify().recover(invalid_link).unify()
+}
+
+// delete exports whose links have expired
+pub(crate) async fn clean_exports() {
+    let ttl = CONFIG
+        .read()
+        .await
+        .download_ttl
+        .unwrap_or(DEFAULT_DOWNLOAD_TTL);
+    let folders = match fs::read_dir(&*EXPORTS_PATH) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+    for folder in folders.flatten() {
+        let expired = folder
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|m| SystemTime::now().duration_since(m).ok())
+            .is_some_and(|age| age > Duration::from_secs(ttl));
+        if !expired {
+            continue;
+        }
+        match fs::remove_dir_all(folder.path()) {
+            Ok(_) => info!("removed expired export {}", folder.path().display()),
+            Err(e) => warn!("failed to remove export {}: {e}", folder.path().display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_with_spaces_round_trip() {
+        let secret = b"secret";
+        let path = "0123/my world.zip";
+        let expires = unix_millis() / 1000 + DEFAULT_DOWNLOAD_TTL;
+        let link = encode(path);
+        assert_eq!(link, "0123/my%20world.zip");
+        let query = Signature {
+            expires,
+            signature: sign(secret, "export", path, expires),
+        };
+        let requested = decode(&link).unwrap();
+        assert!(verify(secret, "export", &requested, &query));
+        assert!(!verify(secret, "backup", &requested, &query));
+        assert!(!verify(secret, "export", &link, &query));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::event::Event;
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub(crate) const DEFAULT_HISTORY_SIZE: usize = 200;
+
+// recent events of each session, kept so clients that reconnect can catch up on what they missed
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct History {
+    sessions: HashMap<String, VecDeque<Event>>,
+}
+
+impl History {
+    pub(crate) fn load<T: AsRef<Path>>(path: T) -> Self {
+        let data = match fs::read_to_string(path.as_ref()) {
+            Ok(v) => v,
+            Err(_) => return Self::default(),
+        };
+        match serde_json::from_str(&data) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("could not read chat history, starting with an empty one: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    // write to a temporary file first so a crash mid write doesn't lose the whole history
+    pub(crate) async fn save<T: AsRef<Path>>(&self, path: T) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let data = serde_json::to_string(self)?;
+        let temp = PathBuf::from(format!("{}.tmp", path.display()));
+        tokio::fs::write(&temp, data).await?;
+        tokio::fs::rename(temp, path).await
+    }
+
+    pub(crate) fn push(&mut self, event: Event, capacity: usize) {
+        let buffer = self.sessions.entry(event.session.clone()).or_default();
+        buffer.push_back(event);
+        while buffer.len() > capacity {
+            buffer.pop_front();
+        }
+    }
+
+    // events of a session after the given timestamp, limited to the most recent ones
+    pub(crate) fn query(
+        &self,
+        session: &str,
+        since: Option<u64>,
+        limit: Option<usize>,
+    ) -> Vec<&Event> {
+        let buffer = match self.sessions.get(session) {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+        let events: Vec<&Event> = buffer
+            .iter()
+            .filter(|e| since.is_none_or(|s| e.timestamp > s))
+            .collect();
+        let skip = limit.map_or(0, |l| events.len().saturating_sub(l));
+        events.into_iter().skip(skip).collect()
+    }
+
+    // the most recent events of every session, oldest first
+    pub(crate) fn recent(&self, limit: usize) -> Vec<&Event> {
+        let mut events: Vec<&Event> = self
+            .sessions
+            .keys()
+            .flat_map(|s| self.query(s, None, Some(limit)))
+            .collect();
+        events.sort_by_key(|e| e.timestamp);
+        events
+    }
+}
@@ -0,0 +1,430 @@
+use crate::{
+    backup::Game,
+    bridge::Session,
+    config::Rcon,
+    parser::LogFlavor,
+    validate::{self, CONFIG_FILES},
+    ws::{CONFIG_DIR, DATA_DIR},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use uuid::Uuid;
+
+const DEFAULT_WS_IP: &str = "127.0.0.1";
+const DEFAULT_WS_PORT: u16 = 7500;
+const DEFAULT_RCON_PORT: u16 = 25575;
+
+// answers given as flags, anything left out is asked for or detected
+#[derive(Default)]
+struct Options {
+    // accept every default without asking
+    yes: bool,
+    // replace files that already exist
+    force: bool,
+    ws_ip: Option<String>,
+    ws_port: Option<String>,
+    backup_location: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{arg} needs a value"))
+            };
+            match arg.as_str() {
+                "-y" | "--yes" => options.yes = true,
+                "--force" => options.force = true,
+                "--ws-ip" => options.ws_ip = Some(value()?),
+                "--ws-port" => options.ws_port = Some(value()?),
+                "--backup-location" => options.backup_location = Some(value()?),
+                _ => return Err(format!("unknown option {arg}")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// ask until the answer is accepted by `valid`, an empty answer takes the default. with --yes the
+// default is used as long as it's valid
+fn ask<F>(yes: bool, question: &str, default: &str, valid: F) -> String
+where
+    F: Fn(&str) -> Result<(), String>,
+{
+    if yes {
+        if let Err(e) = valid(default) {
+            eprintln!("{question}: {e}");
+            std::process::exit(1);
+        }
+        return default.to_owned();
+    }
+    let stdin = io::stdin();
+    loop {
+        match default.is_empty() {
+            true => print!("{question}: "),
+            false => print!("{question} [{default}]: "),
+        }
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        // stdin was closed, nothing else can be asked
+        if stdin.lock().read_line(&mut answer).unwrap_or(0) == 0 {
+            println!();
+            std::process::exit(1);
+        }
+        let answer = match answer.trim() {
+            "" => default,
+            v => v,
+        };
+        match valid(answer) {
+            Ok(_) => return answer.to_owned(),
+            Err(e) => println!("  {e}"),
+        }
+    }
+}
+
+fn confirm(yes: bool, question: &str, default: bool) -> bool {
+    let hint = if default { "Y/n" } else { "y/N" };
+    let answer = ask(yes, &format!("{question} ({hint})"), "", |v| {
+        match v.to_lowercase().as_str() {
+            "" | "y" | "yes" | "n" | "no" => Ok(()),
+            _ => Err("answer y or n".to_owned()),
+        }
+    });
+    match answer.to_lowercase().as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    }
+}
+
+fn anything(_: &str) -> Result<(), String> {
+    Ok(())
+}
+
+// the main config, also written on the first run when there isn't one. values are written as toml
+// strings so paths with quotes or backslashes stay valid
+pub(crate) fn config_text(
+    ws_ip: &str,
+    ws_port: u16,
+    password: &str,
+    backup_location: &str,
+) -> String {
+    let quote = |v: &str| toml::Value::String(v.to_owned()).to_string();
+    format!(
+        r#"# taurus main configuration, run `taurus check` after editing it
+ws_ip = {}
+ws_port = {ws_port}
+# clients have to send this before any command
+ws_password = {}
+backup_location = {}
+"#,
+        quote(ws_ip),
+        quote(password),
+        quote(backup_location)
+    )
+}
+
+// names of the running tmux sessions
+fn tmux_sessions() -> Vec<String> {
+    Command::new("tmux")
+        .args(["list-sessions", "-F", "#{session_name}"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.to_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// folder the session's active pane is in, which is usually where the server was started
+fn tmux_folder(session: &str) -> Option<PathBuf> {
+    let output = Command::new("tmux")
+        .args([
+            "display-message",
+            "-p",
+            "-t",
+            session,
+            "#{pane_current_path}",
+        ])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    Some(PathBuf::from(path)).filter(|p| p.is_dir())
+}
+
+// key=value pairs of server.properties, comments and escapes aside
+fn server_properties(folder: &Path) -> Option<HashMap<String, String>> {
+    let data = fs::read_to_string(folder.join("server.properties")).ok()?;
+    Some(
+        data.lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k.trim().to_owned(), v.trim().replace("\\:", ":")))
+            .collect(),
+    )
+}
+
+// plugin servers are paper or one of its forks, mod loaders log the way forge does
+fn detect_flavor(folder: &Path) -> Option<LogFlavor> {
+    match (
+        folder.join("plugins").is_dir(),
+        folder.join("mods").is_dir(),
+    ) {
+        (true, _) => Some(LogFlavor::Paper),
+        (_, true) => Some(LogFlavor::Forge),
+        _ => None,
+    }
+}
+
+fn rcon(yes: bool, name: &str, properties: &HashMap<String, String>) -> Option<Rcon> {
+    let password = properties.get("rcon.password").filter(|p| !p.is_empty());
+    if properties.get("enable-rcon").map(|v| v.as_str()) != Some("true") || password.is_none() {
+        println!(
+            "  rcon is not enabled in server.properties, set enable-rcon=true and an rcon.password \
+             for chat and player commands to work, then run `taurus init` again"
+        );
+        return None;
+    }
+    let port = properties
+        .get("rcon.port")
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(DEFAULT_RCON_PORT);
+    let port = ask(
+        yes,
+        &format!("rcon port of {name}"),
+        &port.to_string(),
+        |v| {
+            v.parse::<u16>()
+                .ok()
+                .filter(|p| *p != 0)
+                .map(|_| ())
+                .ok_or_else(|| "not a valid port".to_owned())
+        },
+    );
+    Some(Rcon {
+        ip: None,
+        port: port.parse().unwrap_or(DEFAULT_RCON_PORT),
+        password: password?.to_owned(),
+        timeout: None,
+    })
+}
+
+// a session for a tmux session, prefilled from the server.properties in its folder
+fn session(options: &Options, tmux: &str) -> Option<Session> {
+    let yes = options.yes;
+    let folder = tmux_folder(tmux);
+    let properties = folder.as_deref().and_then(server_properties);
+    // with --yes only sessions that are clearly running a server are added
+    let add = match yes {
+        true => properties.is_some(),
+        false => confirm(
+            false,
+            &format!("add tmux session {tmux}"),
+            properties.is_some(),
+        ),
+    };
+    if !add {
+        return None;
+    }
+    // the bridge finds the tmux session by the session's name, so it can't be changed
+    if tmux.contains(char::is_whitespace) {
+        println!("  rename tmux session `{tmux}` to a single word to add it");
+        return None;
+    }
+    let name = tmux.to_owned();
+    let folder = ask(
+        yes,
+        &format!("server folder of {name}"),
+        &folder.map(|f| f.display().to_string()).unwrap_or_default(),
+        |v| match Path::new(v).join("server.properties").exists() {
+            true => Ok(()),
+            false => Err(format!("no server.properties in {v}")),
+        },
+    );
+    let folder = PathBuf::from(folder);
+    let properties = server_properties(&folder).unwrap_or_default();
+    let world = folder.join(properties.get("level-name").map_or("world", |v| v.as_str()));
+    let backup_path = ask(
+        yes,
+        &format!("backup folder of {name}, empty for the main one"),
+        "",
+        anything,
+    );
+    Some(Session {
+        rcon: rcon(yes, &name, &properties),
+        name,
+        description: None,
+        host: "tmux".to_owned(),
+        game: Some(Game {
+            file_path: Some(world.display().to_string()),
+            backup_path: Some(backup_path).filter(|p| !p.is_empty()),
+            chat_bridge: Some(true),
+            log_flavor: detect_flavor(&folder),
+            ..Default::default()
+        }),
+    })
+}
+
+fn write(file: &Path, data: &str, options: &Options) -> bool {
+    if file.exists()
+        && !options.force
+        && !confirm(
+            options.yes,
+            &format!("{} exists, replace it", file.display()),
+            false,
+        )
+    {
+        println!("kept {}", file.display());
+        return false;
+    }
+    match fs::write(file, data) {
+        Ok(_) => {
+            println!("wrote {}", file.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("could not write {}: {e}", file.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+// `taurus init`, writes the main config and a session for every tmux session that runs a server
+pub(crate) fn run(args: &[String]) {
+    let options = match Options::parse(args) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let yes = options.yes;
+    let servers = CONFIG_DIR.join("servers");
+    if let Err(e) = fs::create_dir_all(&servers) {
+        eprintln!("could not create {}: {e}", servers.display());
+        std::process::exit(1);
+    }
+    println!("writing config to {}", CONFIG_DIR.display());
+
+    let ws_ip = ask(
+        yes,
+        "websocket address, 0.0.0.0 to listen on every interface",
+        options.ws_ip.as_deref().unwrap_or(DEFAULT_WS_IP),
+        |v| {
+            v.parse::<Ipv4Addr>()
+                .map(|_| ())
+                .map_err(|_| format!("{v} is not an ipv4 address"))
+        },
+    );
+    let ws_port = ask(
+        yes,
+        "websocket port",
+        options
+            .ws_port
+            .as_deref()
+            .unwrap_or(&DEFAULT_WS_PORT.to_string()),
+        |v| {
+            v.parse::<u16>()
+                .ok()
+                .filter(|p| *p != 0)
+                .map(|_| ())
+                .ok_or_else(|| format!("{v} is not a valid port"))
+        },
+    );
+    let backups = DATA_DIR.join("backups").display().to_string();
+    let backup_location = ask(
+        yes,
+        "backup folder",
+        options.backup_location.as_deref().unwrap_or(&backups),
+        |v| match v.trim().is_empty() {
+            true => Err("can't be empty".to_owned()),
+            false => Ok(()),
+        },
+    );
+    let _ = fs::create_dir_all(&backup_location);
+    let password = Uuid::new_v4().to_simple().to_string();
+    let config = config_text(
+        &ws_ip,
+        ws_port.parse().unwrap_or(DEFAULT_WS_PORT),
+        &password,
+        &backup_location,
+    );
+    // the other format would be read instead of the new file
+    let config_file = CONFIG_DIR.join(CONFIG_FILES[0]);
+    let wrote_config = write(&config_file, &config, &options);
+    if wrote_config {
+        let _ = fs::remove_file(CONFIG_DIR.join(CONFIG_FILES[1]));
+    }
+
+    let tmux = tmux_sessions();
+    if tmux.is_empty() {
+        println!("no tmux sessions found, start the servers in tmux and run `taurus init` again to add them");
+    }
+    for name in tmux {
+        let session = match session(&options, &name) {
+            Some(v) => v,
+            None => continue,
+        };
+        let data = match toml::to_string(&session) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("could not write session {}: {e}", session.name);
+                continue;
+            }
+        };
+        // a session kept as json would be loaded twice under the same name
+        let json = servers.join(format!("{}.json", session.name));
+        if json.exists()
+            && !options.force
+            && !confirm(
+                options.yes,
+                &format!("{} exists, replace it with toml", json.display()),
+                false,
+            )
+        {
+            println!("kept {}", json.display());
+            continue;
+        }
+        if write(
+            &servers.join(format!("{}.toml", session.name)),
+            &data,
+            &options,
+        ) && json.exists()
+        {
+            match fs::remove_file(&json) {
+                Ok(_) => println!("removed {}", json.display()),
+                Err(e) => eprintln!("could not remove {}: {e}", json.display()),
+            }
+        }
+    }
+
+    let errors = validate::check_all(&CONFIG_DIR);
+    for e in &errors {
+        eprintln!("{e}");
+    }
+    if wrote_config {
+        println!("websocket password: {password}");
+    }
+    match errors.is_empty() {
+        true => println!("config is valid, start taurus to use it"),
+        false => {
+            eprintln!("found {} problems", errors.len());
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    event::{self, Event, EventKind},
+    utils::unix_millis,
+};
+use serde_derive::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+// finished jobs kept around for the JOB command
+const MAX_FINISHED_JOBS: usize = 50;
+
+lazy_static::lazy_static! {
+    // locked from blocking threads as well, so this is not a tokio mutex
+    static ref JOBS: Mutex<HashMap<u64, Job>> = Mutex::new(HashMap::new());
+    static ref NEXT_ID: AtomicU64 = AtomicU64::new(1);
+}
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub(crate) enum JobState {
+    Running,
+    Done { result: Value },
+    Failed { error: String },
+}
+
+// a long running task, such as scanning a world, that reports back once it is done
+#[derive(Serialize, Clone)]
+pub(crate) struct Job {
+    pub id: u64,
+    pub kind: String,
+    pub session: String,
+    pub started: u64,
+    pub finished: Option<u64>,
+    // units of work done out of the total, e.g. regions scanned
+    pub done: usize,
+    pub total: usize,
+    #[serde(flatten)]
+    pub state: JobState,
+}
+
+// handed to a job so it can report how far along it is
+pub(crate) struct Progress {
+    id: u64,
+}
+
+impl Progress {
+    pub(crate) fn set(&self, done: usize, total: usize) {
+        if let Some(job) = JOBS.lock().unwrap().get_mut(&self.id) {
+            job.done = done;
+            job.total = total;
+        }
+    }
+}
+
+// run a job on the blocking thread pool, a `job_finished` event is sent once it is done
+pub(crate) fn spawn<F>(kind: &str, session: &str, f: F) -> u64
+where
+    F: FnOnce(&Progress) -> Result<Value, String> + Send + 'static,
+{
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let job = Job {
+        id,
+        kind: kind.to_string(),
+        session: session.to_string(),
+        started: unix_millis(),
+        finished: None,
+        done: 0,
+        total: 0,
+        state: JobState::Running,
+    };
+    {
+        let mut jobs = JOBS.lock().unwrap();
+        jobs.insert(id, job);
+        prune(&mut jobs);
+    }
+    let (kind, session) = (kind.to_string(), session.to_string());
+    tokio::task::spawn_blocking(move || {
+        let state = match f(&Progress { id }) {
+            Ok(result) => JobState::Done { result },
+            Err(error) => JobState::Failed { error },
+        };
+        let ok = matches!(state, JobState::Done { .. });
+        if let Some(job) = JOBS.lock().unwrap().get_mut(&id) {
+            job.finished = Some(unix_millis());
+            job.state = state;
+        }
+        event::emit(Event::new(
+            &session,
+            EventKind::JobFinished { id, job: kind, ok },
+        ));
+    });
+    id
+}
+
+pub(crate) fn get(id: u64) -> Option<Job> {
+    JOBS.lock().unwrap().get(&id).cloned()
+}
+
+pub(crate) fn list() -> Vec<Job> {
+    let mut jobs: Vec<Job> = JOBS.lock().unwrap().values().cloned().collect();
+    jobs.sort_by_key(|j| j.id);
+    jobs
+}
+
+fn prune(jobs: &mut HashMap<u64, Job>) {
+    let mut finished: Vec<u64> = jobs
+        .values()
+        .filter(|j| j.finished.is_some())
+        .map(|j| j.id)
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort_unstable();
+    for id in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+        jobs.remove(id);
+    }
+}
@@ -1,21 +1,49 @@
 mod args;
 mod backup;
 mod bridge;
+mod commands;
+mod component;
 mod config;
+mod ctl;
+mod event;
+mod files;
+mod history;
+mod init;
+mod jobs;
+mod list;
+mod map;
+mod nbt;
+mod overrides;
+mod parser;
+mod paths;
+mod playerdata;
+mod playtime;
+mod rcon;
+mod region;
+mod reload;
+mod routing;
+mod scoreboard;
+mod search;
+mod structure;
+mod sync;
+mod trim;
+mod upload;
 mod utils;
+mod validate;
 mod ws;
 use crate::{
     args::parse_args,
     backup::delete_backups_older_than,
     bridge::{Bridge, Session},
-    utils::Sys,
-    ws::PATH,
+    event::Event,
+    history::DEFAULT_HISTORY_SIZE,
+    utils::{unix_millis, Sys},
+    ws::{CONFIG_DIR, DATA_DIR},
 };
-use bridge::{gen_pipe, replace_formatting, set_lines, update_messages};
+use bridge::{gen_pipe, replace_formatting, update_messages};
 use config::Config;
-use log::{error, info};
+use log::{error, info, warn};
 use notify::{watcher, RecursiveMode, Watcher};
-use regex::Regex;
 use std::{
     collections::HashMap,
     convert::Infallible,
@@ -25,13 +53,18 @@ use std::{
 use tokio::sync::Mutex;
 use utils::Clients;
 use warp::Filter;
-use ws::{ws_handler, ARGS, BRIDGES, CONFIG, SESSIONS};
+use ws::{
+    ws_handler, ARGS, BRIDGES, CONFIG, HISTORY, HISTORY_PATH, PLAYERS, PLAYERS_PATH, SESSIONS,
+};
 
 pub async fn run() {
     let startup = Instant::now();
 
     if ARGS.len() > 1 {
-        parse_args(ARGS.to_vec());
+        parse_args(ARGS.to_vec()).await;
+    }
+    if let Err(e) = std::fs::create_dir_all(&*DATA_DIR) {
+        error!("could not create data folder {}: {e}", DATA_DIR.display());
     }
 
     let clients = Arc::new(Mutex::new(HashMap::new()));
@@ -39,7 +72,24 @@ pub async fn run() {
         .and(warp::ws())
         .and(with_clients(clients.clone()))
         .and_then(ws_handler);
-    let routes = ws_route.with(warp::cors().allow_any_origin());
+    let upload_route = warp::put()
+        .and(warp::path("taurus"))
+        .and(warp::path("upload"))
+        .and(warp::path::tail())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::content_length_limit(upload::MAX_UPLOAD))
+        .and(warp::body::bytes())
+        .and_then(upload::upload_handler);
+    let backup_location = CONFIG.read().await.backup_location.clone();
+    let routes = ws_route
+        .or(upload_route)
+        .or(files::routes(backup_location.into()))
+        .with(
+            warp::cors()
+                .allow_any_origin()
+                .allow_methods(vec!["GET", "PUT"])
+                .allow_headers(vec!["authorization", "content-type"]),
+        );
 
     let mut ip = [0; 4];
     for (i, e) in CONFIG.read().await.ws_ip.to_owned().split('.').enumerate() {
@@ -65,83 +115,131 @@ pub async fn run() {
             "tmux" => gen_pipe(&session.name, false).await,
             _ => {}
         };
-        let enabled = match &session.game {
-            Some(v) => v.chat_bridge,
-            None => None,
-        };
         // Wait for tmux to generate the pipe
         tokio::time::sleep(Duration::from_millis(5)).await;
-        let mut locked = BRIDGES.lock().await;
-        locked.push(Bridge {
-            name: name.to_string(),
-            line: set_lines(name),
-            enabled,
-            state: enabled.unwrap_or_default(),
-        });
+        if let Some(bridge) = Bridge::new(session) {
+            BRIDGES.lock().await.push(bridge);
+        }
     }
 
-    if SESSIONS.read().await.len() > 0 {
-        tokio::spawn(async move {
-            let parse_pattern = Regex::new(r"^\[\d{2}:\d{2}:\d{2}\] \[Server thread/INFO\]: (<.*|[\w §]+ (joined|left) the game)$").unwrap();
-            let bridges = BRIDGES.clone();
-            loop {
-                tokio::time::sleep(Duration::from_millis(333)).await;
-                let mut response: Vec<String> = Vec::new();
-                let mut locked = bridges.lock().await;
-                for session in locked.iter_mut() {
-                    let msg = update_messages(session, &parse_pattern).await;
-                    if let Some(v) = msg {
-                        response.push(v);
-                    }
+    // sessions can be added by a reload, so this runs even if there are none yet
+    tokio::spawn(async move {
+        let bridges = BRIDGES.clone();
+        loop {
+            tokio::time::sleep(Duration::from_millis(333)).await;
+            let mut events: Vec<Event> = Vec::new();
+            let mut locked = bridges.lock().await;
+            for session in locked.iter_mut() {
+                events.append(&mut update_messages(session).await);
+            }
+            // before the events, so a server starting again closes sessions at the time it was
+            // last up
+            let now = unix_millis();
+            let mut players = PLAYERS.lock().await;
+            for bridge in locked.iter() {
+                players.heard(&bridge.name, bridge.up_until(now));
+            }
+            drop(players);
+            events.append(&mut event::take_pending());
+            if events.is_empty() {
+                continue;
+            }
+            let collected = events
+                .iter()
+                .filter(|e| e.kind.is_chat_line())
+                .map(|e| format!("[{}] {}", e.session, e.kind))
+                .collect::<Vec<String>>()
+                .join("\n");
+            let msg = format!("MSG {}", &collected);
+            let msg = replace_formatting(&msg);
+            let ws_clients = clients.lock().await;
+            if msg.trim().len() > 4 {
+                Session::send_chat_to_clients(&locked, &msg).await;
+                for client in (*ws_clients).values() {
+                    client.send(&*msg).await;
+                }
+            }
+            for event in &events {
+                let msg = event.to_message();
+                // events carry session names and config changes, only for clients that logged in
+                for client in (*ws_clients).values().filter(|c| c.authed) {
+                    client.send(&*msg).await;
                 }
-                let collected = &response.join("\n");
-                let msg = format!("MSG {}", &collected);
-                let msg = replace_formatting(&msg);
-                if msg.trim().len() > 4 {
-                    Session::send_chat_to_clients(&locked, &msg).await;
-                    let ws_clients = clients.lock().await;
-                    for client in (*ws_clients).values() {
-                        client.send(&*msg).await;
-                    }
+                commands::handle(event).await;
+            }
+            let capacity = CONFIG
+                .read()
+                .await
+                .history_size
+                .unwrap_or(DEFAULT_HISTORY_SIZE);
+            let mut players = PLAYERS.lock().await;
+            let mut changed = false;
+            for event in &events {
+                changed |= players.record(event);
+            }
+            if changed {
+                if let Err(e) = players.save(&*PLAYERS_PATH).await {
+                    warn!("failed to save player data: {e}");
                 }
             }
-        });
+            drop(players);
+            let mut history = HISTORY.lock().await;
+            for event in events {
+                history.push(event, capacity);
+            }
+            if let Err(e) = history.save(&*HISTORY_PATH).await {
+                warn!("failed to save chat history: {e}");
+            }
+        }
+    });
 
-        tokio::spawn(async move {
-            let (tx, rx) = std::sync::mpsc::channel();
-            let mut watcher = watcher(tx, Duration::from_secs(5)).unwrap();
-            watcher.watch(&*PATH, RecursiveMode::Recursive).unwrap();
-            loop {
-                // Send cannot be sent unless the event is dropped, so we must wait until an event
-                // happens then reload the config and continue
-                while let Ok(notify::DebouncedEvent::Write(_)) = rx.recv() {}
-                *SESSIONS.write().await = Config::load_sessions(PATH.to_owned());
-                *CONFIG.write().await = Config::load_config(PATH.to_owned());
+    // the watcher blocks while waiting for changes, so it gets its own thread
+    let handle = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = watcher(tx, Duration::from_secs(5)).unwrap();
+        watcher
+            .watch(&*CONFIG_DIR, RecursiveMode::Recursive)
+            .unwrap();
+        while let Ok(event) = rx.recv() {
+            if reload::is_config_change(&event) {
+                handle.block_on(reload::reload());
             }
-        });
+        }
+    });
 
-        let mut clock: u64 = 0;
-        let mut sys = Sys::new();
-        sys.refresh();
+    let mut clock: u64 = 0;
+    let mut sys = Sys::new();
+    sys.refresh();
 
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(Duration::from_secs(1)).await;
-                // though this will probably literally never be needed, we can loop forever
-                // max backup interval is u64::MAX
-                clock = clock.wrapping_add(1);
-                for i in &*SESSIONS.read().await {
-                    let game = match &i.game {
-                        Some(v) => v,
-                        None => continue,
-                    };
-                    game.perform_scheduled_backups(i.name.as_str(), clock, &sys)
-                        .await;
-                }
-                // todo if disk is low then reduce keep time
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            // though this will probably literally never be needed, we can loop forever
+            // max backup interval is u64::MAX
+            if reload::schedules_restarted() {
+                clock = 0;
             }
-        });
-    }
+            clock = clock.wrapping_add(1);
+            for i in &*SESSIONS.read().await {
+                let game = match &i.game {
+                    Some(v) => v,
+                    None => continue,
+                };
+                game.perform_scheduled_backups(i.name.as_str(), clock, &sys)
+                    .await;
+            }
+            // todo if disk is low then reduce keep time
+        }
+    });
+
+    // exported files are only useful as long as their download links
+    tokio::spawn(async move {
+        loop {
+            files::clean_exports().await;
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        }
+    });
 
     info!("manager loaded in: {} ms, ", startup.elapsed().as_millis());
 
@@ -0,0 +1,79 @@
+use crate::{bridge, config::Rcon};
+use regex::Regex;
+use serde_derive::Serialize;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+pub(crate) const DEFAULT_LIST_CACHE_TTL: u64 = 5;
+
+lazy_static::lazy_static! {
+    // 1.13+ uses "x of a max of y", older versions "x/y" and bukkit "x out of maximum y"
+    static ref LIST: Regex = Regex::new(
+        r"There are (?P<online>\d+)(?: of a max of |/| out of maximum )(?P<max>\d+) players online[:.]?(?P<players>.*)"
+    ).unwrap();
+    static ref PLAYER: Regex = Regex::new(r"^(?P<name>\S+)(?: \((?P<uuid>[0-9a-fA-F-]{32,36})\))?$").unwrap();
+    static ref FORMATTING: Regex = Regex::new("§.").unwrap();
+    static ref CACHE: Mutex<HashMap<String, (Instant, OnlineList)>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct OnlinePlayer {
+    pub name: String,
+    pub uuid: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct OnlineList {
+    pub online: u32,
+    pub max: u32,
+    pub players: Vec<OnlinePlayer>,
+}
+
+impl OnlineList {
+    pub(crate) fn parse(reply: &str) -> Option<Self> {
+        let reply = FORMATTING.replace_all(reply, "");
+        let captures = LIST.captures(&reply)?;
+        let players = captures["players"]
+            .split(',')
+            .filter_map(|p| PLAYER.captures(p.trim()))
+            .map(|p| OnlinePlayer {
+                name: p["name"].to_string(),
+                uuid: p.name("uuid").map(|u| u.as_str().to_string()),
+            })
+            .collect();
+        Some(Self {
+            online: captures["online"].parse().ok()?,
+            max: captures["max"].parse().ok()?,
+            players,
+        })
+    }
+
+    // ask the server for its player list, with uuids if the version supports it, cached for ttl
+    // seconds so polling doesn't open a connection every time
+    pub(crate) async fn fetch(session: &str, rcon: &Rcon, ttl: u64) -> Option<Self> {
+        if let Some((fetched, list)) = CACHE.lock().await.get(session) {
+            if fetched.elapsed() < Duration::from_secs(ttl) {
+                return Some(list.clone());
+            }
+        }
+        let mut list = None;
+        for cmd in ["list uuids", "list"] {
+            if let Ok(reply) = rcon.rcon_send_with_response(cmd).await {
+                list = Self::parse(&reply);
+            }
+            if list.is_some() {
+                break;
+            }
+        }
+        let list = list?;
+        bridge::heard(session).await;
+        CACHE
+            .lock()
+            .await
+            .insert(session.to_string(), (Instant::now(), list.clone()));
+        Some(list)
+    }
+}
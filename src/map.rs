@@ -0,0 +1,412 @@
+use crate::{
+    jobs::Progress,
+    nbt::Tag,
+    region::{Dimension, World},
+};
+use serde_json::{json, Value};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+// one pixel per block
+const TILE: usize = 512;
+// the nether's roof is skipped by looking for the first air below this height
+const NETHER_CEILING: i64 = 120;
+
+// the folder tiles of a dimension are written to, relative to webserver_location and
+// webserver_prefix
+pub(crate) fn tile_folder(session: &str, dim: Dimension) -> String {
+    format!("map/{session}/{}", dim.name().to_lowercase())
+}
+
+struct Section {
+    y: i64,
+    blocks: Vec<String>,
+    block_data: Vec<i64>,
+    biomes: Vec<String>,
+    biome_data: Vec<i64>,
+}
+
+// palette indices are packed into longs without spanning two of them since 1.16
+fn unpack(data: &[i64], bits: usize, index: usize) -> usize {
+    if bits == 0 {
+        return 0;
+    }
+    let per_long = 64 / bits;
+    let long = data.get(index / per_long).copied().unwrap_or_default() as u64;
+    ((long >> ((index % per_long) * bits)) & ((1 << bits) - 1)) as usize
+}
+
+fn bits(palette: usize, min: usize) -> usize {
+    match palette {
+        0 | 1 => 0,
+        v => min.max((usize::BITS - (v - 1).leading_zeros()) as usize),
+    }
+}
+
+impl Section {
+    // 1.18+ keeps block states and biomes per section, 1.16 and 1.17 only the block states
+    fn parse(section: &Tag) -> Option<Self> {
+        let y = section.get("Y")?.as_i64()?;
+        let (palette, block_data) = match section.get("block_states") {
+            Some(v) => (v.get("palette"), v.get("data")),
+            None => (section.get("Palette"), section.get("BlockStates")),
+        };
+        let names = |list: Option<&Tag>, key: Option<&str>| -> Vec<String> {
+            list.and_then(|l| l.as_list())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|t| match key {
+                    Some(k) => t.get(k)?.as_str(),
+                    None => t.as_str(),
+                })
+                .map(|n| n.trim_start_matches("minecraft:").to_string())
+                .collect()
+        };
+        let biomes = section.get("biomes");
+        Some(Self {
+            y,
+            blocks: names(palette, Some("Name")),
+            block_data: block_data.and_then(|d| d.as_ints()).unwrap_or_default(),
+            biomes: names(biomes.and_then(|b| b.get("palette")), None),
+            biome_data: biomes
+                .and_then(|b| b.get("data"))
+                .and_then(|d| d.as_ints())
+                .unwrap_or_default(),
+        })
+    }
+
+    fn block(&self, x: usize, y: usize, z: usize) -> &str {
+        let index = unpack(
+            &self.block_data,
+            bits(self.blocks.len(), 4),
+            y * 256 + z * 16 + x,
+        );
+        self.blocks.get(index).map_or("air", |b| b.as_str())
+    }
+
+    // biomes are stored for 4x4x4 cells
+    fn biome(&self, x: usize, y: usize, z: usize) -> &str {
+        let index = unpack(
+            &self.biome_data,
+            bits(self.biomes.len(), 1),
+            (y / 4) * 16 + (z / 4) * 4 + x / 4,
+        );
+        self.biomes.get(index).map_or("plains", |b| b.as_str())
+    }
+}
+
+fn is_air(block: &str) -> bool {
+    matches!(block, "air" | "cave_air" | "void_air")
+}
+
+// the highest block of each column of a chunk, with its biome and height
+fn top_blocks(chunk: &Tag, dim: Dimension) -> Vec<Option<(String, String, i64)>> {
+    let root = chunk.get("Level").unwrap_or(chunk);
+    let mut sections: Vec<Section> = root
+        .get("sections")
+        .or_else(|| root.get("Sections"))
+        .and_then(|s| s.as_list())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Section::parse)
+        .filter(|s| !s.blocks.is_empty())
+        .collect();
+    sections.sort_by_key(|s| -s.y);
+    let (top, bottom) = match (sections.first(), sections.last()) {
+        (Some(t), Some(b)) => (t.y * 16 + 15, b.y * 16),
+        _ => return vec![None; 256],
+    };
+    let heights = root
+        .at(&["Heightmaps", "MOTION_BLOCKING"])
+        .and_then(|h| h.as_ints());
+    let min_y = root.get("yPos").and_then(|y| y.as_i64()).unwrap_or(0) * 16;
+    let block_at = |x: usize, y: i64, z: usize| -> Option<(&str, &Section)> {
+        let section = sections.iter().find(|s| s.y == y.div_euclid(16))?;
+        Some((section.block(x, y.rem_euclid(16) as usize, z), section))
+    };
+    let mut columns = Vec::with_capacity(256);
+    for z in 0..16 {
+        for x in 0..16 {
+            let mut y = match (dim, &heights) {
+                (Dimension::Nether, _) => NETHER_CEILING.min(top),
+                (_, Some(h)) => min_y + unpack(h, 9, z * 16 + x) as i64 - 1,
+                (_, None) => top,
+            };
+            let mut roof = dim == Dimension::Nether;
+            let mut found = None;
+            while y >= bottom {
+                if let Some((block, section)) = block_at(x, y, z) {
+                    if roof {
+                        roof = !is_air(block);
+                    } else if !is_air(block) {
+                        let biome = section.biome(x, y.rem_euclid(16) as usize, z);
+                        found = Some((block.to_string(), biome.to_string(), y));
+                        break;
+                    }
+                }
+                y -= 1;
+            }
+            columns.push(found);
+        }
+    }
+    columns
+}
+
+fn rgb(color: u32) -> [u8; 3] {
+    [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+}
+
+fn grass(biome: &str) -> u32 {
+    match biome {
+        b if b.contains("badlands") => 0x90814d,
+        b if b.contains("desert") || b.contains("savanna") => 0xbfb755,
+        b if b.contains("jungle") => 0x59c93c,
+        b if b.contains("swamp") => 0x6a7039,
+        b if b.contains("dark_forest") => 0x507a32,
+        b if b.contains("birch") => 0x88bb67,
+        b if b.contains("cherry") => 0xb6db61,
+        b if b.contains("snowy") || b.contains("frozen") || b.contains("grove") => 0x80b497,
+        b if b.contains("taiga") => 0x86b783,
+        b if b.contains("forest") => 0x79c05a,
+        _ => 0x91bd59,
+    }
+}
+
+fn water(biome: &str) -> u32 {
+    match biome {
+        b if b.contains("swamp") => 0x617b64,
+        b if b.contains("lukewarm") => 0x45adf2,
+        b if b.contains("warm") => 0x43d5ee,
+        b if b.contains("cold") => 0x3d57d6,
+        b if b.contains("frozen") => 0x3938c9,
+        _ => 0x3f76e4,
+    }
+}
+
+const DYES: [(&str, u32); 16] = [
+    ("light_blue", 0x3ab3da),
+    ("light_gray", 0x9d9d97),
+    ("white", 0xf9fffe),
+    ("orange", 0xf9801d),
+    ("magenta", 0xc74ebd),
+    ("yellow", 0xfed83d),
+    ("lime", 0x80c71f),
+    ("pink", 0xf38baa),
+    ("gray", 0x474f52),
+    ("cyan", 0x169c9c),
+    ("purple", 0x8932b8),
+    ("blue", 0x3c44aa),
+    ("brown", 0x835432),
+    ("green", 0x5e7c16),
+    ("red", 0xb02e26),
+    ("black", 0x1d1d21),
+];
+
+// rough map colors, blocks that aren't listed fall back to something close by name
+fn block_color(block: &str, biome: &str) -> [u8; 3] {
+    let color = match block {
+        "grass_block" | "short_grass" | "grass" | "tall_grass" | "fern" | "large_fern" | "vine"
+        | "lily_pad" => grass(biome),
+        b if b.ends_with("leaves") && b.starts_with("cherry") => 0xe8a5c8,
+        b if b.ends_with("leaves") && b.contains("azalea") => 0x657f30,
+        b if b.ends_with("leaves") => (grass(biome) >> 1 & 0x7f7f7f) + 0x203010,
+        "water" | "bubble_column" | "seagrass" | "tall_seagrass" | "kelp" | "kelp_plant" => {
+            water(biome)
+        }
+        "lava" | "magma_block" => 0xd96514,
+        "snow" | "snow_block" | "powder_snow" => 0xf8fefe,
+        "ice" | "packed_ice" | "blue_ice" | "frosted_ice" => 0x91b4fe,
+        "sand" | "sandstone" | "smooth_sandstone" | "cut_sandstone" | "suspicious_sand" => 0xdbd3a0,
+        b if b.starts_with("red_sand") => 0xbe6621,
+        "gravel" | "suspicious_gravel" => 0x857f7e,
+        "dirt" | "coarse_dirt" | "rooted_dirt" | "farmland" | "dirt_path" => 0x866043,
+        "podzol" => 0x5b3f18,
+        "mycelium" => 0x6f6265,
+        "mud" | "muddy_mangrove_roots" => 0x3c3a3d,
+        "clay" => 0xa0a6b3,
+        "moss_block" | "moss_carpet" => 0x596e2d,
+        "netherrack" | "nether_wart_block" | "crimson_nylium" => 0x70302e,
+        "warped_nylium" | "warped_wart_block" => 0x2b8b7a,
+        "soul_sand" | "soul_soil" => 0x513e32,
+        "glowstone" | "shroomlight" => 0xf7c65b,
+        "end_stone" | "end_stone_bricks" => 0xdbde9e,
+        "obsidian" | "crying_obsidian" => 0x1b1729,
+        "bedrock" => 0x555555,
+        "terracotta" => 0x985e43,
+        b if b.starts_with("basalt") || b.contains("blackstone") => 0x3b3a40,
+        b if b.contains("deepslate") => 0x4d4d50,
+        b if b.contains("stone") || b.contains("andesite") || b.ends_with("ore") => 0x7d7d7d,
+        b if b.contains("granite") => 0x9a6a54,
+        b if b.contains("diorite") || b.contains("quartz") || b.contains("calcite") => 0xe2e0d8,
+        b if b.contains("crimson") => 0x943f61,
+        b if b.contains("warped") => 0x3a8e8c,
+        b if b.starts_with("spruce") || b.starts_with("dark_oak") || b.starts_with("mangrove") => {
+            0x654b2f
+        }
+        b if b.starts_with("birch") => 0xc5b47c,
+        b if b.starts_with("oak") || b.starts_with("jungle") || b.starts_with("acacia") => 0x9c7f4e,
+        b if b.contains("copper") => 0xc06c50,
+        b if b.contains("prismarine") => 0x4f9d8f,
+        b if b.contains("purpur") => 0xa97ca9,
+        b if b.contains("mushroom") => 0x926a55,
+        b => DYES
+            .iter()
+            .find(|(dye, _)| b.starts_with(dye))
+            .map_or(0x808080, |(_, c)| *c),
+    };
+    rgb(color)
+}
+
+// a chunk's pixels, slopes are shaded against the block north of them
+fn render_chunk(chunk: &Tag, dim: Dimension) -> Vec<[u8; 4]> {
+    let columns = top_blocks(chunk, dim);
+    let mut pixels = vec![[0; 4]; 256];
+    for z in 0..16 {
+        for x in 0..16 {
+            let (block, biome, y) = match &columns[z * 16 + x] {
+                Some(v) => v,
+                None => continue,
+            };
+            let [r, g, b] = block_color(block, biome);
+            let north = match z {
+                0 => *y,
+                _ => columns[(z - 1) * 16 + x].as_ref().map_or(*y, |c| c.2),
+            };
+            let shade = |c: u8| match y.cmp(&north) {
+                std::cmp::Ordering::Greater => c.saturating_add(24),
+                std::cmp::Ordering::Less => c.saturating_sub(24),
+                std::cmp::Ordering::Equal => c,
+            };
+            pixels[z * 16 + x] = [shade(r), shade(g), shade(b), 255];
+        }
+    }
+    pixels
+}
+
+fn read_tile(path: &Path) -> Option<Vec<u8>> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().ok()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).ok()?;
+    if info.width as usize != TILE
+        || info.height as usize != TILE
+        || info.color_type != png::ColorType::Rgba
+    {
+        return None;
+    }
+    buffer.truncate(info.buffer_size());
+    Some(buffer)
+}
+
+fn write_tile(path: &Path, pixels: &[u8]) -> Result<(), String> {
+    let temp = path.with_extension("png.tmp");
+    let file = File::create(&temp).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), TILE as u32, TILE as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut w| w.write_image_data(pixels))
+        .map_err(|e| e.to_string())?;
+    fs::rename(temp, path).map_err(|e| e.to_string())
+}
+
+// render a region into `r.<x>.<z>.png` in the folder, only chunks saved since the last render
+// are drawn again. returns the amount of chunks that were drawn
+pub(crate) fn render_region(
+    world: &World,
+    dim: Dimension,
+    x: i32,
+    z: i32,
+    folder: &Path,
+) -> Result<usize, String> {
+    let region = world
+        .region(dim, x, z)
+        .map_err(|e| format!("unable to read region: {e}"))?;
+    fs::create_dir_all(folder).map_err(|e| format!("unable to create map folder: {e}"))?;
+    let tile = folder.join(format!("r.{x}.{z}.png"));
+    // the save times of the chunks in the current tile
+    let stamps: PathBuf = folder.join(format!("r.{x}.{z}.json"));
+    let existing = read_tile(&tile);
+    let previous: Vec<u32> = match existing {
+        Some(_) => fs::read(&stamps)
+            .ok()
+            .and_then(|s| serde_json::from_slice(&s).ok())
+            .filter(|s: &Vec<u32>| s.len() == 1024)
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    // without the timestamps every chunk is drawn again
+    let mut pixels = match (existing, previous.is_empty()) {
+        (Some(v), false) => v,
+        _ => vec![0; TILE * TILE * 4],
+    };
+    let mut timestamps = vec![0u32; 1024];
+    let mut drawn = 0;
+    for entry in region.entries() {
+        timestamps[entry.index] = entry.timestamp;
+        if previous.get(entry.index) == Some(&entry.timestamp) {
+            continue;
+        }
+        let chunk = match region.read(&entry) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let (cx, cz) = (
+            chunk.x.rem_euclid(32) as usize,
+            chunk.z.rem_euclid(32) as usize,
+        );
+        for (i, pixel) in render_chunk(&chunk.nbt, dim).into_iter().enumerate() {
+            let (px, pz) = (cx * 16 + i % 16, cz * 16 + i / 16);
+            let offset = (pz * TILE + px) * 4;
+            pixels[offset..offset + 4].copy_from_slice(&pixel);
+        }
+        drawn += 1;
+    }
+    // chunks that were trimmed since the last render leave a hole
+    for (index, stamp) in previous.iter().enumerate() {
+        if *stamp != 0 && timestamps[index] == 0 {
+            for row in 0..16 {
+                let offset = (((index / 32) * 16 + row) * TILE + (index % 32) * 16) * 4;
+                pixels[offset..offset + 64].fill(0);
+            }
+            drawn += 1;
+        }
+    }
+    if drawn > 0 || !tile.exists() {
+        write_tile(&tile, &pixels)?;
+        fs::write(&stamps, json!(timestamps).to_string())
+            .map_err(|e| format!("unable to save chunk timestamps: {e}"))?;
+    }
+    Ok(drawn)
+}
+
+pub(crate) fn render_dimension(
+    world: &World,
+    dim: Dimension,
+    folder: &Path,
+    progress: &Progress,
+) -> Result<Value, String> {
+    let regions = world.regions(dim);
+    if regions.is_empty() {
+        return Err(format!("no {} regions found", dim.name()));
+    }
+    let (mut drawn, mut failed) = (0, Vec::new());
+    for (i, (x, z, _)) in regions.iter().enumerate() {
+        progress.set(i, regions.len());
+        match render_region(world, dim, *x, *z, folder) {
+            Ok(v) => drawn += v,
+            Err(e) => failed.push(json!({ "x": x, "z": z, "error": e })),
+        }
+    }
+    progress.set(regions.len(), regions.len());
+    Ok(json!({
+        "dimension": dim.name(),
+        "tiles": regions.len(),
+        "chunks_drawn": drawn,
+        "failed": failed,
+    }))
+}
@@ -0,0 +1,251 @@
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind, Read, Result},
+};
+
+// nesting deeper than this is rejected instead of overflowing the stack on corrupt data
+const MAX_DEPTH: usize = 512;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Tag {
+    End,
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    pub(crate) fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(v) => v.get(key),
+            _ => None,
+        }
+    }
+
+    // follow a path of compound keys, e.g. `["data", "Objectives"]`
+    pub(crate) fn at(&self, path: &[&str]) -> Option<&Tag> {
+        path.iter().try_fold(self, |tag, key| tag.get(key))
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // any integer tag, widened
+    pub(crate) fn as_i64(&self) -> Option<i64> {
+        match self {
+            Tag::Byte(v) => Some(*v as i64),
+            Tag::Short(v) => Some(*v as i64),
+            Tag::Int(v) => Some(*v as i64),
+            Tag::Long(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Tag::Float(v) => Some(*v as f64),
+            Tag::Double(v) => Some(*v),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    pub(crate) fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // int arrays or lists of numbers, used for positions and uuids
+    pub(crate) fn as_ints(&self) -> Option<Vec<i64>> {
+        match self {
+            Tag::IntArray(v) => Some(v.iter().map(|i| *i as i64).collect()),
+            Tag::LongArray(v) => Some(v.clone()),
+            Tag::ByteArray(v) => Some(v.iter().map(|i| *i as i64).collect()),
+            Tag::List(v) => v.iter().map(|t| t.as_i64()).collect(),
+            _ => None,
+        }
+    }
+
+    // plain json of the tag, numbers lose their nbt type
+    pub(crate) fn to_json(&self) -> Value {
+        match self {
+            Tag::End => Value::Null,
+            Tag::Byte(v) => json!(v),
+            Tag::Short(v) => json!(v),
+            Tag::Int(v) => json!(v),
+            Tag::Long(v) => json!(v),
+            Tag::Float(v) => json!(v),
+            Tag::Double(v) => json!(v),
+            Tag::ByteArray(v) => json!(v),
+            Tag::String(v) => json!(v),
+            Tag::List(v) => Value::Array(v.iter().map(|t| t.to_json()).collect()),
+            Tag::Compound(v) => Value::Object(
+                v.iter()
+                    .map(|(k, t)| (k.to_string(), t.to_json()))
+                    .collect(),
+            ),
+            Tag::IntArray(v) => json!(v),
+            Tag::LongArray(v) => json!(v),
+        }
+    }
+}
+
+// read a named root tag from uncompressed data
+pub(crate) fn read(data: &[u8]) -> Result<(String, Tag)> {
+    let mut reader = Reader { data, pos: 0 };
+    let id = reader.u8()?;
+    if id == 0 {
+        return Ok((String::new(), Tag::End));
+    }
+    let name = reader.string()?;
+    let tag = reader.tag(id, 0)?;
+    Ok((name, tag))
+}
+
+// read a root tag from a file such as level.dat or playerdata, which are usually gzipped
+pub(crate) fn read_compressed(data: &[u8]) -> Result<Tag> {
+    let mut decoded = Vec::new();
+    match data {
+        [0x1f, 0x8b, ..] => GzDecoder::new(data).read_to_end(&mut decoded)?,
+        [0x78, ..] => ZlibDecoder::new(data).read_to_end(&mut decoded)?,
+        _ => return read(data).map(|(_, tag)| tag),
+    };
+    read(&decoded).map(|(_, tag)| tag)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|e| *e <= self.data.len())
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "nbt data ended early"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let len = self.i32()?;
+        if len < 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "negative nbt length"));
+        }
+        Ok(len as usize)
+    }
+
+    // strings are java's modified utf-8, which only differs from utf-8 for null and characters
+    // outside the bmp, lossy conversion is close enough for names and ids
+    fn string(&mut self) -> Result<String> {
+        let len = u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).to_string())
+    }
+
+    fn tag(&mut self, id: u8, depth: usize) -> Result<Tag> {
+        if depth > MAX_DEPTH {
+            return Err(Error::new(ErrorKind::InvalidData, "nbt nested too deep"));
+        }
+        Ok(match id {
+            0 => Tag::End,
+            1 => Tag::Byte(self.u8()? as i8),
+            2 => Tag::Short(self.i16()?),
+            3 => Tag::Int(self.i32()?),
+            4 => Tag::Long(self.i64()?),
+            5 => Tag::Float(f32::from_bits(self.i32()? as u32)),
+            6 => Tag::Double(f64::from_bits(self.i64()? as u64)),
+            7 => {
+                let len = self.len()?;
+                Tag::ByteArray(self.take(len)?.iter().map(|b| *b as i8).collect())
+            }
+            8 => Tag::String(self.string()?),
+            9 => {
+                let item = self.u8()?;
+                let len = self.len()?;
+                // empty lists are written with the end tag as their type
+                if item == 0 {
+                    return Ok(Tag::List(Vec::new()));
+                }
+                // every item takes at least a byte, so this stops bogus lengths allocating
+                let mut list = Vec::with_capacity(len.min(self.data.len() - self.pos));
+                for _ in 0..len {
+                    list.push(self.tag(item, depth + 1)?);
+                }
+                Tag::List(list)
+            }
+            10 => {
+                let mut compound = HashMap::new();
+                loop {
+                    let item = self.u8()?;
+                    if item == 0 {
+                        break;
+                    }
+                    let name = self.string()?;
+                    compound.insert(name, self.tag(item, depth + 1)?);
+                }
+                Tag::Compound(compound)
+            }
+            11 => {
+                let len = self.len()?;
+                let bytes = self.take(len.saturating_mul(4))?;
+                Tag::IntArray(
+                    bytes
+                        .chunks_exact(4)
+                        .map(|b| i32::from_be_bytes(b.try_into().unwrap()))
+                        .collect(),
+                )
+            }
+            12 => {
+                let len = self.len()?;
+                let bytes = self.take(len.saturating_mul(8))?;
+                Tag::LongArray(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|b| i64::from_be_bytes(b.try_into().unwrap()))
+                        .collect(),
+                )
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown nbt tag {id}"),
+                ))
+            }
+        })
+    }
+}
@@ -0,0 +1,257 @@
+use crate::paths::{CONFIG_DIR_ENV, DATA_DIR_ENV};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, env, fs};
+
+// TAURUS_<FIELD> overrides the main config and TAURUS_SERVERS__<NAME>__<FIELD> a session, `__`
+// goes a level deeper, e.g. TAURUS_SERVERS__SMP__RCON__PASSWORD or TAURUS_ROUTES__0__FILTER
+const PREFIX: &str = "TAURUS_";
+const SERVERS: &str = "SERVERS__";
+// `<field>_file` reads a field from a file and `<field>_env` from an environment variable, so
+// secrets don't have to be written into the config
+const FILE_SUFFIX: &str = "_file";
+const ENV_SUFFIX: &str = "_env";
+
+// field to where its value came from, errors about these fields point there instead of a line
+pub(crate) type Sources = HashMap<String, String>;
+
+// a problem with an override, the field and what went wrong
+pub(crate) type OverrideError = (String, String);
+
+// the variables overrides and `_env` fields are read from, the process environment outside of
+// tests
+pub(crate) type Env = HashMap<String, String>;
+
+pub(crate) fn environment() -> Env {
+    env::vars().collect()
+}
+
+// overrides that were read as json, by field, with their json pointer and the text they came
+// from. if the field turns out to want a string they are put back as one
+pub(crate) type Guessed = HashMap<String, (String, String)>;
+
+// session names as they appear in variable names, `my-server` is MY_SERVER
+fn env_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect()
+}
+
+fn join(parent: &str, key: &str) -> String {
+    match (parent.is_empty(), key.parse::<usize>()) {
+        (_, Ok(i)) => format!("{parent}[{i}]"),
+        (true, _) => key.to_owned(),
+        (false, _) => format!("{parent}.{key}"),
+    }
+}
+
+fn pointer(parent: &str, key: &str) -> String {
+    format!("{parent}/{}", key.replace('~', "~0").replace('/', "~1"))
+}
+
+// values are read as json so numbers, lists and tables can be given, unless the file already has
+// a string there. whether a guess was made is returned so it can be undone
+fn env_value(existing: Option<&Value>, raw: &str) -> (Value, bool) {
+    let text = Value::String(raw.to_owned());
+    match existing {
+        Some(Value::String(_)) => (text, false),
+        _ => match serde_json::from_str(raw) {
+            Ok(v @ Value::String(_)) | Ok(v @ Value::Null) => (v, false),
+            Ok(v) => (v, true),
+            Err(_) => (text, false),
+        },
+    }
+}
+
+// a guessed value was the wrong type, use the text it came from instead. false if the field
+// wasn't guessed, so the error stands
+pub(crate) fn as_string(value: &mut Value, guessed: &mut Guessed, field: &str) -> bool {
+    let (pointer, raw) = match guessed.remove(field) {
+        Some(v) => v,
+        None => return false,
+    };
+    match value.pointer_mut(&pointer) {
+        Some(v) => {
+            *v = Value::String(raw);
+            true
+        }
+        None => false,
+    }
+}
+
+fn set(
+    root: &mut Value,
+    keys: &[String],
+    raw: &str,
+    guessed: &mut Guessed,
+) -> Result<String, String> {
+    let mut field = String::new();
+    let mut path = String::new();
+    let mut current = root;
+    for (i, key) in keys.iter().enumerate() {
+        let last = i == keys.len() - 1;
+        field = join(&field, key);
+        path = pointer(&path, key);
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+        let next = match (current, key.parse::<usize>()) {
+            (Value::Array(list), Ok(index)) => {
+                // one past the end adds an entry
+                if index > list.len() {
+                    return Err(format!("{field} is past the end of the list"));
+                }
+                if index == list.len() {
+                    list.push(Value::Null);
+                }
+                &mut list[index]
+            }
+            (Value::Object(map), _) => map.entry(key.as_str()).or_insert(Value::Null),
+            _ => return Err(format!("{field} is not inside a table or list")),
+        };
+        if last {
+            let (value, guess) = env_value(Some(&*next).filter(|v| !v.is_null()), raw);
+            *next = value;
+            if guess {
+                guessed.insert(field.clone(), (path.clone(), raw.to_owned()));
+            }
+        }
+        current = next;
+    }
+    Ok(field)
+}
+
+fn is_folder(var: &str) -> bool {
+    var == CONFIG_DIR_ENV || var == DATA_DIR_ENV
+}
+
+// the variables that apply to the main config, or to the session with this name
+fn variables(env: &Env, session: Option<&str>) -> Vec<(String, Vec<String>, String)> {
+    let prefix = match session {
+        Some(name) => format!("{PREFIX}{SERVERS}{}__", env_name(name)),
+        None => PREFIX.to_owned(),
+    };
+    let mut vars: Vec<(String, Vec<String>, String)> = env
+        .iter()
+        .filter_map(|(name, value)| {
+            let rest = name.strip_prefix(&prefix)?;
+            if session.is_none() && (rest.starts_with(SERVERS) || is_folder(name)) {
+                return None;
+            }
+            let keys: Vec<String> = rest.split("__").map(|k| k.to_lowercase()).collect();
+            if keys.iter().any(|k| k.is_empty()) {
+                return None;
+            }
+            Some((name.clone(), keys, value.clone()))
+        })
+        .collect();
+    vars.sort();
+    vars
+}
+
+// replace every `<field>_file` and `<field>_env` with the value it points to, secrets are kept as
+// strings unless the file has something else there
+fn resolve(
+    value: &mut Value,
+    field: &str,
+    path: &str,
+    env: &Env,
+    sources: &mut Sources,
+    guessed: &mut Guessed,
+) -> Result<(), OverrideError> {
+    match value {
+        Value::Object(map) => {
+            let keys: Vec<String> = map.keys().cloned().collect();
+            for key in keys {
+                let (name, from_file) =
+                    match (key.strip_suffix(FILE_SUFFIX), key.strip_suffix(ENV_SUFFIX)) {
+                        (Some(name), _) => (name.to_owned(), true),
+                        (_, Some(name)) => (name.to_owned(), false),
+                        _ => {
+                            resolve(
+                                &mut map[&key],
+                                &join(field, &key),
+                                &pointer(path, &key),
+                                env,
+                                sources,
+                                guessed,
+                            )?;
+                            continue;
+                        }
+                    };
+                let reference = join(field, &key);
+                let target = match map.get(&key) {
+                    Some(Value::String(v)) => v.clone(),
+                    _ => return Err((reference, "has to be a string".to_owned())),
+                };
+                let (secret, source) = match from_file {
+                    // secret files usually end with a newline that isn't part of the secret
+                    true => fs::read_to_string(&target)
+                        .map(|s| {
+                            (
+                                s.trim_end_matches(['\r', '\n']).to_owned(),
+                                format!("file {target}"),
+                            )
+                        })
+                        .map_err(|e| {
+                            (reference.clone(), format!("unable to read {target}: {e}"))
+                        })?,
+                    false => env
+                        .get(&target)
+                        .map(|s| (s.clone(), format!("environment variable {target}")))
+                        .ok_or_else(|| (reference.clone(), format!("{target} is not set")))?,
+                };
+                map.remove(&key);
+                let existing = map.get(&name).filter(|v| !v.is_null());
+                let (value, guess) =
+                    env_value(existing.or(Some(&Value::String(String::new()))), &secret);
+                map.insert(name.clone(), value);
+                if guess {
+                    guessed.insert(join(field, &name), (pointer(path, &name), secret));
+                }
+                sources.insert(join(field, &name), source);
+            }
+            Ok(())
+        }
+        Value::Array(list) => {
+            for (i, item) in list.iter_mut().enumerate() {
+                let key = i.to_string();
+                resolve(
+                    item,
+                    &join(field, &key),
+                    &pointer(path, &key),
+                    env,
+                    sources,
+                    guessed,
+                )?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+// apply the environment to a parsed config file, sessions are matched by their name
+pub(crate) fn apply(
+    value: &mut Value,
+    session: bool,
+    env: &Env,
+) -> Result<(Sources, Guessed), OverrideError> {
+    let name = match session {
+        true => match value.get("name").and_then(|n| n.as_str()) {
+            Some(v) => Some(v.to_owned()),
+            None => return Ok((Sources::new(), Guessed::new())),
+        },
+        false => None,
+    };
+    let mut sources = Sources::new();
+    let mut guessed = Guessed::new();
+    for (var, keys, raw) in variables(env, name.as_deref()) {
+        let field = set(value, &keys, &raw, &mut guessed).map_err(|e| (var.clone(), e))?;
+        sources.insert(field, format!("environment variable {var}"));
+    }
+    resolve(value, "", "", env, &mut sources, &mut guessed)?;
+    Ok((sources, guessed))
+}
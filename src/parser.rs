@@ -0,0 +1,393 @@
+use crate::event::EventKind;
+use log::error;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+lazy_static::lazy_static! {
+    static ref ANSI: Regex = Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    static ref CHAT: Regex = Regex::new(r"^(?:\[Not Secure\] )?<(?P<player>[^>]+)> (?P<message>.*)$").unwrap();
+    static ref JOIN: Regex = Regex::new(r"^(?P<player>[^<>\[\]]+?) joined the game$").unwrap();
+    static ref LEAVE: Regex = Regex::new(r"^(?P<player>[^<>\[\]]+?) left the game$").unwrap();
+    static ref ADVANCEMENT: Regex = Regex::new(
+        r"^(?P<player>[^<>\[\]]+?) has (?:made the advancement|completed the challenge|reached the goal) \[(?P<advancement>.+)\]$"
+    ).unwrap();
+    // vanilla death messages all start with the player name followed by one of these
+    static ref DEATH: Regex = Regex::new(
+        r"^(?P<player>\w{1,16}) (?P<cause>(?:was|were) .+|drowned.*|died.*|blew up.*|hit the ground too hard.*|fell .+|experienced kinetic energy.*|went (?:up in flames|off with a bang).*|burned to death.*|walked into .+|tried to swim in lava.*|suffocated in a wall.*|starved to death.*|withered away.*|froze to death.*|discovered the floor was lava.*|didn't want to live .+|left the confines of this world.*)$"
+    ).unwrap();
+    static ref STARTING: Regex = Regex::new(r"^Starting minecraft server version (?P<version>.+)$").unwrap();
+    static ref STARTED: Regex = Regex::new(r"^Done \((?P<seconds>\d+(?:[.,]\d+)?)s\)!").unwrap();
+    static ref LAG: Regex = Regex::new(
+        r"^Can't keep up! Is the server overloaded\? Running (?P<ms>\d+)ms or (?P<ticks>\d+) ticks behind$"
+    ).unwrap();
+}
+
+// the kind of server whose output is piped into taurus, each one formats its log lines differently
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogFlavor {
+    #[default]
+    Vanilla,
+    #[serde(alias = "spigot", alias = "bukkit", alias = "purpur")]
+    Paper,
+    #[serde(alias = "fabric", alias = "quilt", alias = "neoforge")]
+    Forge,
+    Velocity,
+    #[serde(alias = "waterfall")]
+    Bungee,
+    // uses the pattern from `log_pattern`
+    Custom,
+}
+
+// turns a raw line of server output into an event, the body is everything after the log prefix
+pub(crate) trait LogParser: Send + Sync {
+    fn body<'a>(&self, line: &'a str) -> Option<&'a str>;
+
+    fn classify(&self, body: &str) -> Option<EventKind> {
+        classify(body)
+    }
+}
+
+impl LogFlavor {
+    // a custom flavor needs a pattern, if it is missing or invalid we fall back to vanilla
+    pub(crate) fn parser(&self, pattern: Option<&str>) -> Box<dyn LogParser> {
+        match self {
+            Self::Vanilla => Box::new(PrefixParser::new(
+                r"^\[[^\]]+\] \[Server thread/(?:INFO|WARN)\]: (?P<body>.*)$",
+            )),
+            Self::Forge => Box::new(PrefixParser::new(
+                r"^\[[^\]]+\] \[Server thread/(?:INFO|WARN)\](?: \[[^\]]*\]:| \([^)]*\)|:) (?P<body>.*)$",
+            )),
+            Self::Paper => Box::new(PrefixParser::new(
+                r"^\[\d{2}:\d{2}:\d{2} (?:INFO|WARN)\]: (?P<body>.*)$",
+            )),
+            Self::Velocity => Box::new(ProxyParser {
+                prefix: PrefixParser::new(r"^\[\d{2}:\d{2}:\d{2} INFO\]: (?P<body>.*)$"),
+                join: Regex::new(
+                    r"^\[connected player\] (?P<player>\S+) \([^)]*\) has connected$",
+                )
+                .unwrap(),
+                leave: Regex::new(
+                    r"^\[connected player\] (?P<player>\S+) \([^)]*\) has disconnected$",
+                )
+                .unwrap(),
+            }),
+            Self::Bungee => Box::new(ProxyParser {
+                prefix: PrefixParser::new(
+                    r"^(?:\[\d{2}:\d{2}:\d{2} INFO\]:|\d{2}:\d{2}:\d{2} \[INFO\]) (?P<body>.*)$",
+                ),
+                join: Regex::new(
+                    r"^\[(?:/[^|\]]*\|(?P<player>[^\]]+)|(?P<legacy>[^,\]]+),/[^\]]*)\] <-> InitialHandler has connected$",
+                )
+                .unwrap(),
+                leave: Regex::new(r"^\[(?P<player>[^\]]+)\] -> UpstreamBridge has disconnected$")
+                    .unwrap(),
+            }),
+            Self::Custom => match pattern.map(Regex::new) {
+                Some(Ok(prefix)) => Box::new(PrefixParser { prefix }),
+                Some(Err(e)) => {
+                    error!("invalid log_pattern, falling back to vanilla: {e}");
+                    Self::Vanilla.parser(None)
+                }
+                None => {
+                    error!("custom log flavor requires a log_pattern, falling back to vanilla");
+                    Self::Vanilla.parser(None)
+                }
+            },
+        }
+    }
+}
+
+// strip terminal escape codes and carriage returns that end up in the tmux pipe
+pub(crate) fn clean_line(line: &str) -> Cow<'_, str> {
+    let line = line.trim_end_matches('\r');
+    if line.contains('\x1b') {
+        return Cow::Owned(ANSI.replace_all(line, "").into_owned());
+    }
+    Cow::Borrowed(line)
+}
+
+// shared between every flavor that logs in the same format as the vanilla server thread
+pub(crate) fn classify(body: &str) -> Option<EventKind> {
+    if let Some(c) = CHAT.captures(body) {
+        return Some(EventKind::Chat {
+            player: c["player"].to_string(),
+            message: c["message"].to_string(),
+        });
+    }
+    if let Some(c) = JOIN.captures(body) {
+        return Some(EventKind::PlayerJoin {
+            player: c["player"].to_string(),
+        });
+    }
+    if let Some(c) = LEAVE.captures(body) {
+        return Some(EventKind::PlayerLeave {
+            player: c["player"].to_string(),
+        });
+    }
+    if let Some(c) = ADVANCEMENT.captures(body) {
+        return Some(EventKind::Advancement {
+            player: c["player"].to_string(),
+            advancement: c["advancement"].to_string(),
+        });
+    }
+    if let Some(c) = DEATH.captures(body) {
+        return Some(EventKind::Death {
+            player: c["player"].to_string(),
+            cause: c["cause"].to_string(),
+        });
+    }
+    if let Some(c) = STARTING.captures(body) {
+        return Some(EventKind::ServerStarting {
+            version: c["version"].to_string(),
+        });
+    }
+    if let Some(c) = STARTED.captures(body) {
+        return Some(EventKind::ServerStarted {
+            seconds: c["seconds"].replace(',', ".").parse().unwrap_or_default(),
+        });
+    }
+    if let Some(c) = LAG.captures(body) {
+        return Some(EventKind::Lag {
+            ms: c["ms"].parse().unwrap_or_default(),
+            ticks: c["ticks"].parse().unwrap_or_default(),
+        });
+    }
+    match body {
+        "Stopping the server" | "Stopping server" => Some(EventKind::ServerStopping),
+        _ => None,
+    }
+}
+
+// a parser that only differs by the prefix in front of each line, the body is taken from the
+// `body` group or the first capture group if there is none
+struct PrefixParser {
+    prefix: Regex,
+}
+
+impl PrefixParser {
+    fn new(pattern: &str) -> Self {
+        Self {
+            prefix: Regex::new(pattern).unwrap(),
+        }
+    }
+}
+
+impl LogParser for PrefixParser {
+    fn body<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let captures = self.prefix.captures(line)?;
+        captures
+            .name("body")
+            .or_else(|| captures.get(1))
+            .map(|m| m.as_str())
+    }
+}
+
+// proxies don't have chat or a server thread, only players connecting and disconnecting
+struct ProxyParser {
+    prefix: PrefixParser,
+    join: Regex,
+    leave: Regex,
+}
+
+impl LogParser for ProxyParser {
+    fn body<'a>(&self, line: &'a str) -> Option<&'a str> {
+        self.prefix.body(line)
+    }
+
+    fn classify(&self, body: &str) -> Option<EventKind> {
+        if let Some(c) = self.join.captures(body) {
+            let player = c.name("player").or_else(|| c.name("legacy"))?;
+            return Some(EventKind::PlayerJoin {
+                player: player.as_str().to_string(),
+            });
+        }
+        if let Some(c) = self.leave.captures(body) {
+            return Some(EventKind::PlayerLeave {
+                player: c["player"].to_string(),
+            });
+        }
+        // velocity and bungee respectively
+        if body.starts_with("Shutting down the proxy") || body.starts_with("Closing listener") {
+            return Some(EventKind::ServerStopping);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(flavor: LogFlavor, pattern: Option<&str>, line: &str) -> Option<EventKind> {
+        let parser = flavor.parser(pattern);
+        parser.classify(parser.body(line)?)
+    }
+
+    fn join(player: &str) -> Option<EventKind> {
+        Some(EventKind::PlayerJoin {
+            player: player.to_string(),
+        })
+    }
+
+    fn leave(player: &str) -> Option<EventKind> {
+        Some(EventKind::PlayerLeave {
+            player: player.to_string(),
+        })
+    }
+
+    #[test]
+    fn vanilla() {
+        assert_eq!(
+            event(
+                LogFlavor::Vanilla,
+                None,
+                "[12:34:56] [Server thread/INFO]: <Steve> hello there"
+            ),
+            Some(EventKind::Chat {
+                player: "Steve".to_string(),
+                message: "hello there".to_string(),
+            })
+        );
+        assert_eq!(
+            event(
+                LogFlavor::Vanilla,
+                None,
+                "[12:34:56] [Server thread/INFO]: Starting minecraft server version 1.20.4"
+            ),
+            Some(EventKind::ServerStarting {
+                version: "1.20.4".to_string(),
+            })
+        );
+        // other threads aren't the server talking
+        assert_eq!(
+            event(
+                LogFlavor::Vanilla,
+                None,
+                "[12:34:56] [Worker-Main-1/INFO]: Steve joined the game"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn forge() {
+        assert_eq!(
+            event(
+                LogFlavor::Forge,
+                None,
+                "[12Jan2024 12:34:56.789] [Server thread/INFO] [net.minecraft.server.MinecraftServer/]: Steve joined the game"
+            ),
+            join("Steve")
+        );
+        // fabric puts the logger in parentheses
+        assert_eq!(
+            event(
+                LogFlavor::Forge,
+                None,
+                "[12:34:56] [Server thread/INFO] (Minecraft) Steve left the game"
+            ),
+            leave("Steve")
+        );
+    }
+
+    #[test]
+    fn paper() {
+        assert_eq!(
+            event(
+                LogFlavor::Paper,
+                None,
+                "[12:34:56 INFO]: Done (3,215s)! For help, type \"help\""
+            ),
+            Some(EventKind::ServerStarted { seconds: 3.215 })
+        );
+        assert_eq!(
+            event(LogFlavor::Paper, None, "[12:34:56 INFO]: Stopping server"),
+            Some(EventKind::ServerStopping)
+        );
+    }
+
+    #[test]
+    fn velocity() {
+        assert_eq!(
+            event(
+                LogFlavor::Velocity,
+                None,
+                "[12:34:56 INFO]: [connected player] Steve (/127.0.0.1:52431) has connected"
+            ),
+            join("Steve")
+        );
+        // proxies don't have chat
+        assert_eq!(
+            event(LogFlavor::Velocity, None, "[12:34:56 INFO]: <Steve> hi"),
+            None
+        );
+    }
+
+    #[test]
+    fn bungee() {
+        assert_eq!(
+            event(
+                LogFlavor::Bungee,
+                None,
+                "[12:34:56 INFO]: [/127.0.0.1:52431|Steve] <-> InitialHandler has connected"
+            ),
+            join("Steve")
+        );
+        assert_eq!(
+            event(
+                LogFlavor::Bungee,
+                None,
+                "12:34:56 [INFO] [Steve,/127.0.0.1:52431] <-> InitialHandler has connected"
+            ),
+            join("Steve")
+        );
+        assert_eq!(
+            event(
+                LogFlavor::Bungee,
+                None,
+                "12:34:56 [INFO] [Steve] -> UpstreamBridge has disconnected"
+            ),
+            leave("Steve")
+        );
+    }
+
+    #[test]
+    fn custom() {
+        // without a `body` group the first capture is used
+        assert_eq!(
+            event(
+                LogFlavor::Custom,
+                Some(r"^\w+ > (.*)$"),
+                "INFO > Steve joined the game"
+            ),
+            join("Steve")
+        );
+        assert_eq!(
+            event(
+                LogFlavor::Custom,
+                Some(r"^(?P<level>\w+) \| (?P<body>.*)$"),
+                "INFO | Steve left the game"
+            ),
+            leave("Steve")
+        );
+        // an invalid pattern falls back to vanilla
+        assert_eq!(
+            event(
+                LogFlavor::Custom,
+                Some("(unclosed"),
+                "[12:34:56] [Server thread/INFO]: Steve joined the game"
+            ),
+            join("Steve")
+        );
+    }
+
+    #[test]
+    fn escape_codes() {
+        assert_eq!(
+            clean_line("\x1b[32m[12:34:56 INFO]: Steve joined the game\x1b[0m\r"),
+            "[12:34:56 INFO]: Steve joined the game"
+        );
+    }
+}
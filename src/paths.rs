@@ -0,0 +1,79 @@
+use crate::validate::CONFIG_FILES;
+use std::{env, path::PathBuf};
+
+const CONFIG_DIR_FLAG: &str = "--config-dir";
+const DATA_DIR_FLAG: &str = "--data-dir";
+// these pick folders, they aren't config fields to override
+pub(crate) const CONFIG_DIR_ENV: &str = "TAURUS_CONFIG_DIR";
+pub(crate) const DATA_DIR_ENV: &str = "TAURUS_DATA_DIR";
+
+// `--flag <dir>` or `--flag=<dir>`
+fn flag(args: &[String], name: &str) -> Option<PathBuf> {
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.strip_prefix(name)?.strip_prefix('=') {
+            Some(v) => Some(PathBuf::from(v)),
+            None if arg == name => args.get(i + 1).map(PathBuf::from),
+            None => None,
+        })
+}
+
+// the arguments without the folder flags, so commands only see their own arguments
+pub(crate) fn strip_flags(args: Vec<String>) -> Vec<String> {
+    let mut stripped = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == CONFIG_DIR_FLAG || arg == DATA_DIR_FLAG {
+            args.next();
+            continue;
+        }
+        if arg.starts_with(&format!("{CONFIG_DIR_FLAG}="))
+            || arg.starts_with(&format!("{DATA_DIR_FLAG}="))
+        {
+            continue;
+        }
+        stripped.push(arg);
+    }
+    stripped
+}
+
+// $XDG_<kind>_HOME/taurus, or the usual folder in the home directory when it isn't set
+fn xdg(kind: &str, fallback: &str) -> PathBuf {
+    let base = env::var_os(format!("XDG_{kind}_HOME"))
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(fallback)))
+        .unwrap_or_default();
+    base.join("taurus")
+}
+
+// setups from before these options kept their config next to the binary
+fn beside_binary() -> Option<PathBuf> {
+    let dir = env::current_exe().ok()?.parent()?.to_path_buf();
+    CONFIG_FILES
+        .iter()
+        .any(|f| dir.join(f).exists())
+        .then_some(dir)
+}
+
+// --config-dir, TAURUS_CONFIG_DIR, a config next to the binary, then $XDG_CONFIG_HOME/taurus
+pub(crate) fn config_dir(args: &[String]) -> PathBuf {
+    flag(args, CONFIG_DIR_FLAG)
+        .or_else(|| env::var_os(CONFIG_DIR_ENV).map(PathBuf::from))
+        .or_else(beside_binary)
+        .unwrap_or_else(|| xdg("CONFIG", ".config"))
+}
+
+// history, player data and exports, --data-dir, TAURUS_DATA_DIR, then $XDG_DATA_HOME/taurus. a
+// config kept next to the binary keeps its data there as well
+pub(crate) fn data_dir(args: &[String]) -> PathBuf {
+    flag(args, DATA_DIR_FLAG)
+        .or_else(|| env::var_os(DATA_DIR_ENV).map(PathBuf::from))
+        .or_else(|| {
+            match flag(args, CONFIG_DIR_FLAG).is_none() && env::var_os(CONFIG_DIR_ENV).is_none() {
+                true => beside_binary(),
+                false => None,
+            }
+        })
+        .unwrap_or_else(|| xdg("DATA", ".local/share"))
+}
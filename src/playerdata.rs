@@ -0,0 +1,141 @@
+use crate::{
+    backup::{Game, Snapshot},
+    nbt::{self, Tag},
+};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct CachedUser {
+    name: String,
+    uuid: String,
+}
+
+// dashed lowercase uuid if the text is one, with or without dashes
+fn parse_uuid(text: &str) -> Option<String> {
+    let hex: String = text.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = hex.to_lowercase();
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+// find the uuid and name of a player through the server's usercache.json
+async fn resolve(game: &Game, player: &str) -> Result<(String, Option<String>), String> {
+    let users: Vec<CachedUser> = match game.server_file("usercache.json") {
+        Some(path) => match tokio::fs::read_to_string(path).await {
+            Ok(v) => serde_json::from_str(&v).unwrap_or_default(),
+            Err(_) => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+    if let Some(uuid) = parse_uuid(player) {
+        let name = users
+            .into_iter()
+            .find(|u| u.uuid.eq_ignore_ascii_case(&uuid))
+            .map(|u| u.name);
+        return Ok((uuid, name));
+    }
+    users
+        .into_iter()
+        .find(|u| u.name.eq_ignore_ascii_case(player))
+        .map(|u| (u.uuid.to_lowercase(), Some(u.name)))
+        .ok_or_else(|| "Player not found in usercache.json".to_owned())
+}
+
+fn items(list: Option<&Tag>) -> Value {
+    let list = match list.and_then(|l| l.as_list()) {
+        Some(v) => v,
+        None => return json!([]),
+    };
+    list.iter()
+        .map(|stack| {
+            json!({
+                "slot": stack.get("Slot").and_then(|s| s.as_i64()),
+                "id": stack.get("id").and_then(|i| i.as_str()),
+                "count": stack
+                    .get("Count")
+                    .or_else(|| stack.get("count"))
+                    .and_then(|c| c.as_i64())
+                    .unwrap_or(1),
+                // enchantments, names and contents, `tag` before 1.20.5
+                "components": stack
+                    .get("components")
+                    .or_else(|| stack.get("tag"))
+                    .map(|c| c.to_json()),
+            })
+        })
+        .collect()
+}
+
+// everything the world stores about a player, from the live world or a backup
+pub(crate) async fn player_info(
+    game: &Game,
+    player: &str,
+    snapshot: &Snapshot,
+) -> Result<Value, String> {
+    let (uuid, name) = resolve(game, player).await?;
+    let data = game
+        .read_world_file(snapshot, &format!("playerdata/{uuid}.dat"))
+        .await?;
+    let nbt = nbt::read_compressed(&data).map_err(|e| format!("invalid player data: {e}"))?;
+    let read_json = |file: Result<Vec<u8>, String>| {
+        file.ok()
+            .and_then(|f| serde_json::from_slice::<Value>(&f).ok())
+            .unwrap_or_default()
+    };
+    let stats = read_json(
+        game.read_world_file(snapshot, &format!("stats/{uuid}.json"))
+            .await,
+    );
+    let advancements = read_json(
+        game.read_world_file(snapshot, &format!("advancements/{uuid}.json"))
+            .await,
+    );
+    // recipes are unlocked as advancements too, but aren't interesting here
+    let mut completed: Vec<&String> = advancements
+        .as_object()
+        .map(|a| {
+            a.iter()
+                .filter(|(k, v)| !k.contains(":recipes/") && v["done"] == json!(true))
+                .map(|(k, _)| k)
+                .collect()
+        })
+        .unwrap_or_default();
+    completed.sort();
+    let pos: Vec<f64> = nbt
+        .get("Pos")
+        .and_then(|p| p.as_list())
+        .map(|p| p.iter().filter_map(|v| v.as_f64()).collect())
+        .unwrap_or_default();
+    let field = |key: &str| nbt.get(key).map(|v| v.to_json());
+    Ok(json!({
+        "uuid": uuid,
+        "name": name,
+        "snapshot": snapshot.name(),
+        "position": {
+            "x": pos.first(),
+            "y": pos.get(1),
+            "z": pos.get(2),
+            "dimension": field("Dimension"),
+        },
+        "health": field("Health"),
+        "food": field("foodLevel"),
+        "xp_level": field("XpLevel"),
+        "game_mode": field("playerGameType"),
+        "inventory": items(nbt.get("Inventory")),
+        "ender_chest": items(nbt.get("EnderItems")),
+        // armor and offhand moved out of the inventory in 1.21.5
+        "equipment": field("equipment"),
+        "stats": stats.get("stats").cloned().unwrap_or_default(),
+        "advancements": completed,
+    }))
+}
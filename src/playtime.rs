@@ -0,0 +1,196 @@
+use crate::event::{Event, EventKind};
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// amount of finished sessions kept for each player
+const MAX_PLAY_SESSIONS: usize = 50;
+
+// all timestamps and durations are in ms
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub(crate) struct PlaySession {
+    pub join: u64,
+    pub leave: u64,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct PlayerRecord {
+    pub first_join: u64,
+    pub last_seen: u64,
+    // total of every finished session
+    pub playtime: u64,
+    // join time of the current session while the player is online
+    pub online_since: Option<u64>,
+    pub sessions: Vec<PlaySession>,
+}
+
+impl PlayerRecord {
+    fn close(&mut self, leave: u64) {
+        if let Some(join) = self.online_since.take() {
+            let leave = leave.max(join);
+            self.playtime += leave - join;
+            self.last_seen = leave;
+            self.sessions.push(PlaySession { join, leave });
+            if self.sessions.len() > MAX_PLAY_SESSIONS {
+                self.sessions.remove(0);
+            }
+        }
+    }
+
+    // playtime including the session that is currently open
+    pub(crate) fn total(&self, now: u64) -> u64 {
+        self.playtime + self.online_since.map_or(0, |j| now.saturating_sub(j))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct ServerPlayers {
+    // last time the server was surely up, open sessions are closed at this time if it went down
+    // without logging players leaving
+    pub last_activity: u64,
+    pub players: HashMap<String, PlayerRecord>,
+}
+
+impl ServerPlayers {
+    fn close_all(&mut self, leave: u64) {
+        for record in self.players.values_mut() {
+            record.close(leave);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct PlayerStore {
+    servers: HashMap<String, ServerPlayers>,
+}
+
+impl PlayerStore {
+    pub(crate) fn load<T: AsRef<Path>>(path: T) -> Self {
+        let data = match fs::read_to_string(path.as_ref()) {
+            Ok(v) => v,
+            Err(_) => return Self::default(),
+        };
+        match serde_json::from_str(&data) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("could not read player data, starting with an empty store: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    pub(crate) async fn save<T: AsRef<Path>>(&self, path: T) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let data = serde_json::to_string(self)?;
+        let temp = PathBuf::from(format!("{}.tmp", path.display()));
+        tokio::fs::write(&temp, data).await?;
+        tokio::fs::rename(temp, path).await
+    }
+
+    // the server logged something or answered rcon, see Bridge::up_until
+    pub(crate) fn heard(&mut self, session: &str, time: u64) {
+        if let Some(server) = self.servers.get_mut(session) {
+            server.last_activity = server.last_activity.max(time);
+        }
+    }
+
+    // update the store from an event, returns if anything about the players changed
+    pub(crate) fn record(&mut self, event: &Event) -> bool {
+        let server = self.servers.entry(event.session.clone()).or_default();
+        let previous_activity = server.last_activity;
+        server.last_activity = server.last_activity.max(event.timestamp);
+        match &event.kind {
+            EventKind::PlayerJoin { player } => {
+                let record = server.players.entry(player.clone()).or_default();
+                // a second join without a leave means we missed the leave, end it here
+                record.close(event.timestamp);
+                if record.first_join == 0 {
+                    record.first_join = event.timestamp;
+                }
+                record.last_seen = event.timestamp;
+                record.online_since = Some(event.timestamp);
+            }
+            EventKind::PlayerLeave { player } => {
+                if let Some(record) = server.players.get_mut(player) {
+                    record.close(event.timestamp);
+                }
+            }
+            // a server starting with players still online crashed or was killed, so they left at
+            // the last time we heard from it
+            EventKind::ServerStarting { .. } | EventKind::ServerStarted { .. } => {
+                server.close_all(previous_activity);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn find<'a>(&'a self, player: &str) -> Vec<(&'a str, &'a str, &'a PlayerRecord)> {
+        let mut found = Vec::new();
+        for (server, players) in &self.servers {
+            for (name, record) in &players.players {
+                if name.eq_ignore_ascii_case(player) {
+                    found.push((server.as_str(), name.as_str(), record));
+                }
+            }
+        }
+        found
+    }
+
+    pub(crate) fn playtime(&self, player: &str, now: u64) -> Value {
+        let mut response = serde_json::Map::new();
+        for (server, _, record) in self.find(player) {
+            response.insert(
+                server.to_string(),
+                json!({
+                    "playtime": record.total(now),
+                    "first_join": record.first_join,
+                    "last_seen": record.last_seen,
+                    "online": record.online_since.is_some(),
+                    "sessions": record.sessions.len() + record.online_since.map_or(0, |_| 1),
+                }),
+            );
+        }
+        Value::Object(response)
+    }
+
+    // the most recent time a player was seen on any server
+    pub(crate) fn last_seen(&self, player: &str) -> Option<Value> {
+        let (server, name, record) = self
+            .find(player)
+            .into_iter()
+            .max_by_key(|(_, _, r)| (r.online_since.is_some(), r.last_seen))?;
+        Some(json!({
+            "player": name,
+            "session": server,
+            "last_seen": record.last_seen,
+            "online": record.online_since.is_some(),
+        }))
+    }
+
+    pub(crate) fn top_playtime(&self, session: &str, limit: usize, now: u64) -> Value {
+        let server = match self.servers.get(session) {
+            Some(v) => v,
+            None => return json!([]),
+        };
+        let mut players: Vec<(&String, u64)> = server
+            .players
+            .iter()
+            .map(|(name, record)| (name, record.total(now)))
+            .collect();
+        players.sort_by_key(|p| Reverse(p.1));
+        players.truncate(limit);
+        Value::Array(
+            players
+                .into_iter()
+                .map(|(player, playtime)| json!({ "player": player, "playtime": playtime }))
+                .collect(),
+        )
+    }
+}
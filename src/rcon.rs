@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind, Result},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::Mutex,
+    time::timeout,
+};
+
+pub(crate) const DEFAULT_RCON_TIMEOUT: u64 = 5000;
+
+const AUTH: i32 = 3;
+const AUTH_RESPONSE: i32 = 2;
+const EXEC_COMMAND: i32 = 2;
+const RESPONSE_VALUE: i32 = 0;
+// responses are fragmented at 4096 bytes, anything far above that is a broken stream
+const MAX_PACKET_SIZE: i32 = 1 << 16;
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+lazy_static::lazy_static! {
+    static ref POOL: Mutex<HashMap<String, Arc<Mutex<Pooled>>>> = Mutex::new(HashMap::new());
+}
+
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: Vec<u8>,
+}
+
+// an authenticated connection to a server
+struct Connection {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl Connection {
+    async fn open(address: &str, password: &str) -> Result<Self> {
+        let mut conn = Self {
+            stream: TcpStream::connect(address).await?,
+            next_id: 0,
+        };
+        let id = conn.id();
+        conn.write(id, AUTH, password).await?;
+        // some servers send an empty response value before the auth response
+        loop {
+            let packet = conn.read().await?;
+            if packet.kind != AUTH_RESPONSE {
+                continue;
+            }
+            if packet.id == -1 {
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    "rcon authentication failed",
+                ));
+            }
+            return Ok(conn);
+        }
+    }
+
+    fn id(&mut self) -> i32 {
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        self.next_id
+    }
+
+    async fn write(&mut self, id: i32, kind: i32, body: &str) -> Result<()> {
+        let mut packet = Vec::with_capacity(body.len() + 14);
+        packet.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        self.stream.write_all(&packet).await
+    }
+
+    async fn read(&mut self) -> Result<Packet> {
+        let len = self.stream.read_i32_le().await?;
+        if !(10..=MAX_PACKET_SIZE).contains(&len) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid rcon packet length {len}"),
+            ));
+        }
+        let mut data = vec![0; len as usize];
+        self.stream.read_exact(&mut data).await?;
+        let id = i32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let kind = i32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        // strip the body and padding null terminators
+        data.truncate(data.len() - 2);
+        Ok(Packet {
+            id,
+            kind,
+            body: data.split_off(8),
+        })
+    }
+
+    // long responses are split over several packets, so an empty response value packet is sent
+    // after the command. the server answers packets in order, so once the reply to it comes back
+    // every fragment of the command has been read
+    async fn exec(&mut self, cmd: &str) -> Result<String> {
+        let id = self.id();
+        let end = self.id();
+        self.write(id, EXEC_COMMAND, cmd).await?;
+        self.write(end, RESPONSE_VALUE, "").await?;
+        let mut response = Vec::new();
+        let mut received = false;
+        loop {
+            let packet = match self.read().await {
+                Ok(v) => v,
+                // the server already got the command, make sure it isn't retried
+                Err(e) if received => return Err(Error::other(e)),
+                Err(e) => return Err(e),
+            };
+            received = true;
+            if packet.id == end {
+                break;
+            }
+            if packet.id == id {
+                response.extend_from_slice(&packet.body);
+            }
+        }
+        Ok(String::from_utf8_lossy(&response).to_string())
+    }
+}
+
+// a connection kept open between commands, reconnecting with a backoff when it fails
+#[derive(Default)]
+struct Pooled {
+    conn: Option<Connection>,
+    password: String,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl Pooled {
+    async fn connect(&mut self, address: &str, password: &str, wait: Duration) -> Result<()> {
+        if self.conn.is_some() && self.password == password {
+            return Ok(());
+        }
+        if let Some(retry_at) = self.retry_at {
+            if Instant::now() < retry_at {
+                return Err(Error::new(
+                    ErrorKind::ConnectionRefused,
+                    "waiting before reconnecting to rcon",
+                ));
+            }
+        }
+        let conn = match timeout(wait, Connection::open(address, password)).await {
+            Ok(v) => v,
+            Err(_) => Err(Error::new(ErrorKind::TimedOut, "rcon connection timed out")),
+        };
+        match conn {
+            Ok(v) => {
+                self.conn = Some(v);
+                self.password = password.to_string();
+                self.failures = 0;
+                self.retry_at = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.conn = None;
+                self.failures = self.failures.saturating_add(1);
+                let backoff = Duration::from_millis(500)
+                    .saturating_mul(2u32.saturating_pow(self.failures.min(8)))
+                    .min(MAX_BACKOFF);
+                self.retry_at = Some(Instant::now() + backoff);
+                Err(e)
+            }
+        }
+    }
+
+    async fn exec(&mut self, cmd: &str, wait: Duration) -> Result<String> {
+        let conn = match &mut self.conn {
+            Some(v) => v,
+            None => return Err(Error::new(ErrorKind::NotConnected, "rcon not connected")),
+        };
+        let response = match timeout(wait, conn.exec(cmd)).await {
+            Ok(v) => v,
+            Err(_) => Err(Error::new(ErrorKind::TimedOut, "rcon command timed out")),
+        };
+        // the stream is in an unknown state after an error, so start over next time
+        if response.is_err() {
+            self.conn = None;
+        }
+        response
+    }
+}
+
+// drop the pooled connection so the next command logs in again, e.g. after the password changed
+pub(crate) async fn disconnect(address: &str) {
+    POOL.lock().await.remove(address);
+}
+
+// run a command over the pooled connection to the address, if the connection dropped since the
+// last command (e.g. from a server restart) it is reopened once
+pub(crate) async fn exec(
+    address: &str,
+    password: &str,
+    cmd: &str,
+    wait: Duration,
+) -> Result<String> {
+    let pooled = POOL
+        .lock()
+        .await
+        .entry(address.to_string())
+        .or_default()
+        .clone();
+    let mut pooled = pooled.lock().await;
+    let reused = pooled.conn.is_some();
+    pooled.connect(address, password, wait).await?;
+    match pooled.exec(cmd, wait).await {
+        // only retry when the old connection was closed under us, otherwise the command might
+        // run twice
+        Err(e)
+            if reused
+                && matches!(
+                    e.kind(),
+                    ErrorKind::BrokenPipe
+                        | ErrorKind::ConnectionReset
+                        | ErrorKind::ConnectionAborted
+                        | ErrorKind::UnexpectedEof
+                ) =>
+        {
+            pooled.connect(address, password, wait).await?;
+            pooled.exec(cmd, wait).await
+        }
+        response => response,
+    }
+}
@@ -0,0 +1,361 @@
+use crate::nbt::{self, Tag};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use log::warn;
+use std::{
+    collections::HashSet,
+    fs,
+    io::{Error, ErrorKind, Read, Result},
+    path::{Path, PathBuf},
+};
+
+pub(crate) const SECTOR: usize = 4096;
+// chunk data is spread over these folders of a dimension
+pub(crate) const CHUNK_FOLDERS: [&str; 3] = ["region", "entities", "poi"];
+// offsets followed by timestamps, one entry per chunk
+const HEADER: usize = SECTOR * 2;
+const LZ4_MAGIC: &[u8] = b"LZ4Block";
+// chunks are a few MiB at most once decompressed, anything bigger is broken or made to use up
+// memory, regions can come from uploads
+const MAX_CHUNK_DATA: usize = 32 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Dimension {
+    Overworld,
+    Nether,
+    End,
+}
+
+impl Dimension {
+    // same names the websocket commands already use
+    pub(crate) fn parse(dim: &str) -> Option<Self> {
+        match dim.to_uppercase().as_str() {
+            "OW" | "OVERWORLD" => Some(Self::Overworld),
+            "NETHER" => Some(Self::Nether),
+            "END" => Some(Self::End),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Overworld => "OW",
+            Self::Nether => "NETHER",
+            Self::End => "END",
+        }
+    }
+
+    // the folder of a dimension inside a world, `kind` is region, entities or poi
+    pub(crate) fn folder(&self, world: &Path, kind: &str) -> PathBuf {
+        match self {
+            Self::Overworld => world.join(kind),
+            Self::Nether => world.join("DIM-1").join(kind),
+            Self::End => world.join("DIM1").join(kind),
+        }
+    }
+}
+
+// a world folder, usually a session's file_path
+pub(crate) struct World {
+    pub path: PathBuf,
+}
+
+impl World {
+    pub(crate) fn new<T: AsRef<Path>>(path: T) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub(crate) fn region_path(&self, dim: Dimension, x: i32, z: i32) -> PathBuf {
+        dim.folder(&self.path, "region")
+            .join(format!("r.{x}.{z}.mca"))
+    }
+
+    // coordinates and paths of every region file of a dimension
+    pub(crate) fn regions(&self, dim: Dimension) -> Vec<(i32, i32, PathBuf)> {
+        let dir = match dim.folder(&self.path, "region").read_dir() {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
+        };
+        let mut regions: Vec<(i32, i32, PathBuf)> = dir
+            .flatten()
+            .filter_map(|f| {
+                let (x, z) = parse_region_name(&f.file_name().to_string_lossy())?;
+                Some((x, z, f.path()))
+            })
+            .collect();
+        regions.sort_by_key(|r| (r.0, r.1));
+        regions
+    }
+
+    pub(crate) fn region(&self, dim: Dimension, x: i32, z: i32) -> Result<Region> {
+        Region::open(self.region_path(dim, x, z))
+    }
+}
+
+// `r.<x>.<z>.mca`
+pub(crate) fn parse_region_name(name: &str) -> Option<(i32, i32)> {
+    let coords = name.strip_prefix("r.")?.strip_suffix(".mca")?;
+    let (x, z) = coords.split_once('.')?;
+    Some((x.parse().ok()?, z.parse().ok()?))
+}
+
+// where a chunk is stored in a region file
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ChunkEntry {
+    // index in the header, x + z * 32 relative to the region
+    pub index: usize,
+    // absolute chunk coordinates
+    pub x: i32,
+    pub z: i32,
+    pub sector: usize,
+    pub sectors: usize,
+    // unix seconds of the last time the chunk was saved
+    pub timestamp: u32,
+}
+
+pub(crate) struct Chunk {
+    pub x: i32,
+    pub z: i32,
+    pub timestamp: u32,
+    pub nbt: Tag,
+}
+
+pub(crate) struct Region {
+    pub path: PathBuf,
+    pub x: i32,
+    pub z: i32,
+    data: Vec<u8>,
+}
+
+impl Region {
+    pub(crate) fn open<T: AsRef<Path>>(path: T) -> Result<Self> {
+        let data = fs::read(path.as_ref())?;
+        Self::from_data(path, data)
+    }
+
+    // a region that isn't on disk yet, e.g. an upload, `path` is where it would be stored
+    pub(crate) fn from_data<T: AsRef<Path>>(path: T, data: Vec<u8>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (x, z) = path
+            .file_name()
+            .and_then(|n| parse_region_name(&n.to_string_lossy()))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "not a region file name"))?;
+        // an empty file is a region the server created but never wrote chunks to
+        if !data.is_empty() && data.len() < HEADER {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "region header is cut off",
+            ));
+        }
+        Ok(Self { path, x, z, data })
+    }
+
+    // chunks that have been generated in this region
+    pub(crate) fn entries(&self) -> Vec<ChunkEntry> {
+        if self.data.is_empty() {
+            return Vec::new();
+        }
+        (0..1024)
+            .filter_map(|index| {
+                let location = &self.data[index * 4..index * 4 + 4];
+                let sector = u32::from_be_bytes([0, location[0], location[1], location[2]]);
+                let sectors = location[3] as usize;
+                if sector < 2 || sectors == 0 {
+                    return None;
+                }
+                let timestamp = &self.data[SECTOR + index * 4..SECTOR + index * 4 + 4];
+                Some(ChunkEntry {
+                    index,
+                    x: self.x * 32 + (index % 32) as i32,
+                    z: self.z * 32 + (index / 32) as i32,
+                    sector: sector as usize,
+                    sectors,
+                    timestamp: u32::from_be_bytes(timestamp.try_into().unwrap()),
+                })
+            })
+            .collect()
+    }
+
+    // the compression type and payload of a chunk as stored on disk
+    pub(crate) fn raw(&self, entry: &ChunkEntry) -> Result<(u8, &[u8])> {
+        let start = entry.sector * SECTOR;
+        let header = self
+            .data
+            .get(start..start + 5)
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "chunk is outside the region"))?;
+        let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let payload = self
+            .data
+            .get(start + 5..(start + 4 + len).max(start + 5))
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "chunk is cut off"))?;
+        Ok((header[4], payload))
+    }
+
+    pub(crate) fn read(&self, entry: &ChunkEntry) -> Result<Chunk> {
+        let (compression, payload) = self.raw(entry)?;
+        // the high bit means the chunk was too big for the region and is in its own file
+        let external;
+        let payload = if compression & 128 != 0 {
+            let path = self
+                .path
+                .with_file_name(format!("c.{}.{}.mcc", entry.x, entry.z));
+            external = fs::read(path)?;
+            &external[..]
+        } else {
+            payload
+        };
+        let data = decompress(compression & 127, payload)?;
+        let (_, nbt) = nbt::read(&data)?;
+        Ok(Chunk {
+            x: entry.x,
+            z: entry.z,
+            timestamp: entry.timestamp,
+            nbt,
+        })
+    }
+
+    // the region file without the given chunks, the rest are packed one after another. empty
+    // if no chunks are left
+    pub(crate) fn without(&self, remove: &HashSet<usize>) -> Vec<u8> {
+        let kept: Vec<(&Region, ChunkEntry)> = self
+            .entries()
+            .into_iter()
+            .filter(|e| !remove.contains(&e.index))
+            .map(|e| (self, e))
+            .collect();
+        pack(&kept)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(crate) fn into_chunks(self) -> impl Iterator<Item = Chunk> {
+        self.entries()
+            .into_iter()
+            .filter_map(move |entry| match self.read(&entry) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    warn!(
+                        "could not read chunk {} {} in {}: {e}",
+                        entry.x,
+                        entry.z,
+                        self.path.display()
+                    );
+                    None
+                }
+            })
+    }
+}
+
+// a region file made of chunks copied as they are stored from other regions, each chunk goes in
+// the slot of its entry's index. empty if there are no chunks
+pub(crate) fn pack(chunks: &[(&Region, ChunkEntry)]) -> Vec<u8> {
+    if chunks.is_empty() {
+        return Vec::new();
+    }
+    let mut data = vec![0; HEADER];
+    for (region, entry) in chunks {
+        let start = (entry.sector * SECTOR).min(region.data.len());
+        let end = (start + entry.sectors * SECTOR).min(region.data.len());
+        let sector = data.len() / SECTOR;
+        data.extend_from_slice(&region.data[start..end]);
+        data.resize((sector + entry.sectors) * SECTOR, 0);
+        let location = ((sector as u32) << 8 | entry.sectors as u32).to_be_bytes();
+        data[entry.index * 4..entry.index * 4 + 4].copy_from_slice(&location);
+        let timestamp = SECTOR + entry.index * 4;
+        data[timestamp..timestamp + 4].copy_from_slice(&entry.timestamp.to_be_bytes());
+    }
+    data
+}
+
+// write next to the region and rename so a failure can't leave half a region behind
+pub(crate) fn write_region(path: &Path, data: &[u8]) -> Result<()> {
+    if data.is_empty() {
+        return fs::remove_file(path);
+    }
+    let temp = path.with_extension("mca.tmp");
+    fs::write(&temp, data)?;
+    fs::rename(temp, path)
+}
+
+pub(crate) fn decompress(compression: u8, payload: &[u8]) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    match compression {
+        1 => {
+            GzDecoder::new(payload)
+                .take(MAX_CHUNK_DATA as u64 + 1)
+                .read_to_end(&mut data)?;
+        }
+        2 => {
+            ZlibDecoder::new(payload)
+                .take(MAX_CHUNK_DATA as u64 + 1)
+                .read_to_end(&mut data)?;
+        }
+        3 => data.extend_from_slice(payload),
+        4 => data = lz4_block(payload)?,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported chunk compression {compression}"),
+            ))
+        }
+    }
+    if data.len() > MAX_CHUNK_DATA {
+        return Err(Error::new(ErrorKind::InvalidData, "chunk is too large"));
+    }
+    Ok(data)
+}
+
+// lz4 chunks use the block stream of lz4-java: a magic, a token with the method, the compressed
+// and decompressed lengths and a checksum, repeated until an empty block
+fn lz4_block(mut payload: &[u8]) -> Result<Vec<u8>> {
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+    let mut data = Vec::new();
+    while !payload.is_empty() {
+        if payload.len() < 21 || &payload[..8] != LZ4_MAGIC {
+            return Err(invalid("invalid lz4 block header"));
+        }
+        let method = payload[8] & 0xf0;
+        let compressed = u32::from_le_bytes(payload[9..13].try_into().unwrap()) as usize;
+        let decompressed = u32::from_le_bytes(payload[13..17].try_into().unwrap()) as usize;
+        if decompressed == 0 {
+            break;
+        }
+        // checked before decompressing, the length is allocated up front
+        if data.len() + decompressed > MAX_CHUNK_DATA {
+            return Err(invalid("chunk is too large"));
+        }
+        let block = payload
+            .get(21..21 + compressed)
+            .ok_or_else(|| invalid("lz4 block is cut off"))?;
+        match method {
+            0x10 => data.extend_from_slice(block),
+            0x20 => data.extend_from_slice(
+                &lz4_flex::block::decompress(block, decompressed)
+                    .map_err(|e| invalid(&e.to_string()))?,
+            ),
+            _ => return Err(invalid("unknown lz4 block method")),
+        }
+        payload = &payload[21 + compressed..];
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_lz4_block() {
+        // a compressed block claiming to decompress to 4 GiB
+        let mut payload = LZ4_MAGIC.to_vec();
+        payload.push(0x20);
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&u32::MAX.to_le_bytes());
+        payload.extend_from_slice(&[0; 5]);
+        let error = decompress(4, &payload).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{
+    bridge::{close_pipe, gen_pipe, Bridge, Session},
+    config::Config,
+    event::{self, Event, EventKind},
+    rcon,
+    validate::CONFIG_FILES,
+    ws::{BRIDGES, CONFIG, CONFIG_DIR, SESSIONS},
+};
+use log::{error, info, warn};
+use notify::DebouncedEvent;
+use serde_json::Value;
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+// the websocket server and file routes are set up once, these need a restart to change
+const RESTART_REQUIRED: [&str; 3] = ["ws_ip", "ws_port", "backup_location"];
+
+// set when sessions were added or changed so the backup scheduler counts from the reload
+static RESTART_SCHEDULES: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn schedules_restarted() -> bool {
+    RESTART_SCHEDULES.swap(false, Ordering::Relaxed)
+}
+
+// history, player data and exports live next to the config and are written all the time, only
+// the config files themselves should trigger a reload
+pub(crate) fn is_config_change(event: &DebouncedEvent) -> bool {
+    let is_config = |path: &Path| {
+        let in_servers = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .is_some_and(|p| p == "servers");
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        CONFIG_FILES.contains(&name.as_ref())
+            || (in_servers && (name.ends_with(".json") || name.ends_with(".toml")))
+    };
+    match event {
+        DebouncedEvent::Create(p) | DebouncedEvent::Write(p) | DebouncedEvent::Remove(p) => {
+            is_config(p)
+        }
+        DebouncedEvent::Rename(from, to) => is_config(from) || is_config(to),
+        _ => false,
+    }
+}
+
+// names of top level config fields that differ, values are left out since some are passwords
+fn changed_fields(old: &Config, new: &Config) -> Vec<String> {
+    let (old, new) = match (serde_json::to_value(old), serde_json::to_value(new)) {
+        (Ok(Value::Object(o)), Ok(Value::Object(n))) => (o, n),
+        _ => return Vec::new(),
+    };
+    let mut fields: Vec<String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|k| old.get(*k) != new.get(*k))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+fn same(old: &Session, new: &Session) -> bool {
+    serde_json::to_value(old).ok() == serde_json::to_value(new).ok()
+}
+
+// load the config again and apply what changed, if anything in it is invalid the running config
+// is kept
+pub(crate) async fn reload() {
+    let path = CONFIG_DIR.as_path();
+    let (config, sessions) = match (
+        Config::try_load_config(path),
+        Config::try_load_sessions(path),
+    ) {
+        (Ok(c), Ok(s)) => (c, s),
+        (config, sessions) => {
+            for e in config.err().into_iter().chain(sessions.err()).flatten() {
+                error!("{e}");
+            }
+            warn!("config not reloaded, keeping the previous one");
+            return;
+        }
+    };
+    let old_sessions = SESSIONS.read().await.clone();
+    let find = |list: &[Session], name: &str| list.iter().find(|s| s.name == name).cloned();
+    let removed: Vec<Session> = old_sessions
+        .iter()
+        .filter(|s| find(&sessions, &s.name).is_none())
+        .cloned()
+        .collect();
+    let added: Vec<&Session> = sessions
+        .iter()
+        .filter(|s| find(&old_sessions, &s.name).is_none())
+        .collect();
+    let changed: Vec<(Session, &Session)> = sessions
+        .iter()
+        .filter_map(|s| Some((find(&old_sessions, &s.name)?, s)))
+        .filter(|(old, new)| !same(old, new))
+        .collect();
+    let fields = changed_fields(&*CONFIG.read().await, &config);
+    if removed.is_empty() && added.is_empty() && changed.is_empty() && fields.is_empty() {
+        return;
+    }
+
+    let mut bridges = BRIDGES.lock().await;
+    for session in &removed {
+        bridges.retain(|b| b.name != session.name);
+        if session.host.trim() == "tmux" {
+            close_pipe(&session.name).await;
+        }
+    }
+    for session in &added {
+        if session.host.trim() == "tmux" {
+            gen_pipe(&session.name, false).await;
+            // Wait for tmux to generate the pipe
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        bridges.extend(Bridge::new(session));
+    }
+    for (old, new) in &changed {
+        match (old.host.trim() == "tmux", new.host.trim() == "tmux") {
+            (false, true) => gen_pipe(&new.name, false).await,
+            (true, false) => close_pipe(&new.name).await,
+            _ => {}
+        }
+        // a new password or address has to log in again
+        if let Some(rcon) = &old.rcon {
+            rcon::disconnect(&rcon.address()).await;
+        }
+        let previous = bridges
+            .iter()
+            .position(|b| b.name == new.name)
+            .map(|i| bridges.remove(i));
+        // keep reading the pipe where the old bridge was, and keep it toggled the same way
+        if let Some(mut bridge) = Bridge::new(new) {
+            if let Some(previous) = previous {
+                bridge.line = previous.line;
+                bridge.running = previous.running;
+                bridge.heard = previous.heard;
+                bridge.quiet = previous.quiet;
+                if previous.enabled == bridge.enabled {
+                    bridge.state = previous.state;
+                }
+            }
+            bridges.push(bridge);
+        }
+    }
+    drop(bridges);
+
+    for field in fields
+        .iter()
+        .filter(|f| RESTART_REQUIRED.contains(&f.as_str()))
+    {
+        warn!("{field} changed, restart taurus for it to take effect");
+    }
+    if !added.is_empty() || !changed.is_empty() {
+        RESTART_SCHEDULES.store(true, Ordering::Relaxed);
+    }
+    let names = |list: Vec<&Session>| list.into_iter().map(|s| s.name.clone()).collect();
+    let kind = EventKind::ConfigReloaded {
+        added: names(added),
+        removed: names(removed.iter().collect()),
+        changed: names(changed.iter().map(|(_, new)| *new).collect()),
+        config: fields,
+    };
+    info!("{kind}");
+    *SESSIONS.write().await = sessions;
+    *CONFIG.write().await = config;
+    event::emit(Event::new("taurus", kind));
+}
@@ -0,0 +1,202 @@
+use log::error;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// one way relay of chat from a session, frontend or channel to other sessions or channels
+#[derive(Deserialize, Serialize, Clone)]
+pub(crate) struct Route {
+    pub from: String,
+    pub to: Vec<String>,
+    // replaces the `[origin]` prefix in game
+    pub prefix: Option<String>,
+    pub color: Option<String>,
+    // only relay messages matching this regex
+    pub filter: Option<String>,
+    // never relay messages matching this regex
+    pub exclude: Option<String>,
+}
+
+// how a message should look once it arrives at a session
+#[derive(Default, Clone)]
+pub(crate) struct Delivery {
+    pub prefix: Option<String>,
+    pub color: Option<String>,
+}
+
+struct CompiledRoute {
+    from: String,
+    to: Vec<String>,
+    delivery: Delivery,
+    filter: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+// decides which sessions receive chat from which origins, sessions in the same channel share all
+// chat while routes relay in one direction only. anything not mentioned in a channel or route is
+// broadcast to every session like before
+#[derive(Default)]
+pub(crate) struct Router {
+    channels: HashMap<String, Vec<String>>,
+    routes: Vec<CompiledRoute>,
+}
+
+impl Router {
+    pub(crate) fn new(channels: Option<&HashMap<String, Vec<String>>>, routes: &[Route]) -> Self {
+        let compile = |pattern: &Option<String>| match pattern.as_deref().map(Regex::new) {
+            Some(Ok(v)) => Some(v),
+            Some(Err(e)) => {
+                error!("invalid route filter, ignoring it: {e}");
+                None
+            }
+            None => None,
+        };
+        Self {
+            channels: channels.cloned().unwrap_or_default(),
+            routes: routes
+                .iter()
+                .map(|r| CompiledRoute {
+                    from: r.from.clone(),
+                    to: r.to.clone(),
+                    delivery: Delivery {
+                        prefix: r.prefix.clone(),
+                        color: r.color.clone(),
+                    },
+                    filter: compile(&r.filter),
+                    exclude: compile(&r.exclude),
+                })
+                .collect(),
+        }
+    }
+
+    // whether a name refers to the target directly or to a channel containing it
+    fn matches(&self, name: &str, target: &str) -> bool {
+        name == target
+            || self
+                .channels
+                .get(name)
+                .is_some_and(|c| c.iter().any(|s| s == target))
+    }
+
+    fn is_routed(&self, origin: &str) -> bool {
+        self.channels.values().flatten().any(|s| s == origin)
+            || self.routes.iter().any(|r| self.matches(&r.from, origin))
+    }
+
+    // how a message from the origin should be delivered to the target, None if it shouldn't be
+    pub(crate) fn route(&self, origin: &str, target: &str, message: &str) -> Option<Delivery> {
+        if origin == target {
+            return None;
+        }
+        if !self.is_routed(origin) {
+            return Some(Delivery::default());
+        }
+        let shared_channel = self
+            .channels
+            .values()
+            .any(|c| c.iter().any(|s| s == origin) && c.iter().any(|s| s == target));
+        if shared_channel {
+            return Some(Delivery::default());
+        }
+        self.routes
+            .iter()
+            .filter(|r| self.matches(&r.from, origin))
+            .filter(|r| r.to.iter().any(|t| self.matches(t, target)))
+            .filter(|r| r.filter.as_ref().is_none_or(|f| f.is_match(message)))
+            .find(|r| !r.exclude.as_ref().is_some_and(|f| f.is_match(message)))
+            .map(|r| r.delivery.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(from: &str, to: &[&str]) -> Route {
+        Route {
+            from: from.to_string(),
+            to: to.iter().map(|t| t.to_string()).collect(),
+            prefix: None,
+            color: None,
+            filter: None,
+            exclude: None,
+        }
+    }
+
+    fn router() -> Router {
+        let channels = HashMap::from([(
+            "survival".to_string(),
+            vec!["smp".to_string(), "creative".to_string()],
+        )]);
+        let routes = [
+            Route {
+                prefix: Some("[D]".to_string()),
+                color: Some("blue".to_string()),
+                ..route("discord", &["survival"])
+            },
+            Route {
+                exclude: Some("^!".to_string()),
+                ..route("survival", &["lobby"])
+            },
+            route("lobby", &["missing"]),
+        ];
+        Router::new(Some(&channels), &routes)
+    }
+
+    #[test]
+    fn channels() {
+        let router = router();
+        assert!(router.route("smp", "creative", "hi").is_some());
+        assert!(router.route("creative", "smp", "hi").is_some());
+        // nothing is sent back to where it came from
+        assert!(router.route("smp", "smp", "hi").is_none());
+    }
+
+    #[test]
+    fn routes() {
+        let router = router();
+        // a channel as the target reaches each of its sessions
+        let delivery = router.route("discord", "creative", "hi").unwrap();
+        assert_eq!(delivery.prefix.as_deref(), Some("[D]"));
+        assert_eq!(delivery.color.as_deref(), Some("blue"));
+        // and as the origin covers each of them, in one direction only
+        assert!(router.route("smp", "lobby", "hi").is_some());
+        assert!(router.route("smp", "lobby", "!cmd").is_none());
+        assert!(router.route("lobby", "smp", "hi").is_none());
+        assert!(router.route("smp", "discord", "hi").is_none());
+    }
+
+    #[test]
+    fn unknown() {
+        let router = router();
+        // routes to a target that doesn't exist send nothing anywhere else
+        assert!(router.route("lobby", "creative", "hi").is_none());
+        assert!(router.route("lobby", "missing", "hi").is_some());
+        // origins no channel or route mentions are broadcast like before
+        let delivery = router.route("hub", "smp", "hi").unwrap();
+        assert!(delivery.prefix.is_none() && delivery.color.is_none());
+    }
+
+    #[test]
+    fn underscores() {
+        let channels = HashMap::from([(
+            "main".to_string(),
+            vec!["smp_1".to_string(), "smp_2".to_string()],
+        )]);
+        let router = Router::new(Some(&channels), &[route("smp_1", &["lobby_hub"])]);
+        assert!(router.route("smp_1", "smp_1", "hi").is_none());
+        assert!(router.route("smp_1", "smp_2", "hi").is_some());
+        assert!(router.route("smp_1", "lobby_hub", "hi").is_some());
+        assert!(router.route("smp_1", "creative", "hi").is_none());
+    }
+
+    #[test]
+    fn invalid_filter() {
+        let routes = [Route {
+            filter: Some("(unclosed".to_string()),
+            ..route("smp", &["lobby"])
+        }];
+        let router = Router::new(None, &routes);
+        assert!(router.route("smp", "lobby", "hi").is_some());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::nbt;
+use serde_json::{json, Value};
+use std::{cmp::Reverse, collections::HashMap};
+
+pub(crate) const SCOREBOARD_FILE: &str = "data/scoreboard.dat";
+
+// scores of every player for an objective, None if the objective doesn't exist in the file
+pub(crate) fn scores(data: &[u8], objective: &str) -> Result<Option<HashMap<String, i64>>, String> {
+    let root = nbt::read_compressed(data).map_err(|e| format!("invalid scoreboard: {e}"))?;
+    let exists = root
+        .at(&["data", "Objectives"])
+        .and_then(|o| o.as_list())
+        .unwrap_or_default()
+        .iter()
+        .any(|o| o.get("Name").and_then(|n| n.as_str()) == Some(objective));
+    if !exists {
+        return Ok(None);
+    }
+    let mut scores = HashMap::new();
+    let entries = root
+        .at(&["data", "PlayerScores"])
+        .and_then(|s| s.as_list())
+        .unwrap_or_default();
+    for entry in entries {
+        if entry.get("Objective").and_then(|o| o.as_str()) != Some(objective) {
+            continue;
+        }
+        if let (Some(name), Some(score)) = (
+            entry.get("Name").and_then(|n| n.as_str()),
+            entry.get("Score").and_then(|s| s.as_i64()),
+        ) {
+            scores.insert(name.to_string(), score);
+        }
+    }
+    Ok(Some(scores))
+}
+
+// players whose score changed, biggest gain first, players missing on one side count as 0
+pub(crate) fn diff(before: &HashMap<String, i64>, after: &HashMap<String, i64>) -> Vec<Value> {
+    let mut changes: Vec<(&String, i64, i64)> = before
+        .keys()
+        .chain(after.keys().filter(|k| !before.contains_key(*k)))
+        .map(|player| {
+            (
+                player,
+                before.get(player).copied().unwrap_or_default(),
+                after.get(player).copied().unwrap_or_default(),
+            )
+        })
+        .filter(|(_, b, a)| a != b)
+        .collect();
+    changes.sort_by_key(|(player, b, a)| (Reverse(a - b), player.to_string()));
+    changes
+        .into_iter()
+        .map(|(player, b, a)| json!({ "player": player, "before": b, "after": a, "change": a - b }))
+        .collect()
+}
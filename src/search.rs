@@ -0,0 +1,229 @@
+use crate::{
+    jobs::Progress,
+    nbt::Tag,
+    region::{Dimension, Region, World},
+};
+use serde_derive::Serialize;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+
+// shulker boxes in shulker boxes can't be placed, but bundles can nest a few times
+const MAX_NESTING: usize = 8;
+// larger areas can scan the whole dimension instead
+const MAX_RADIUS: i64 = 100_000;
+// the world border, nothing is stored past it
+const MAX_COORD: i64 = 30_000_000;
+
+fn coord(value: &str) -> Option<i64> {
+    value.parse::<i64>().ok().filter(|v| v.abs() <= MAX_COORD)
+}
+
+// which part of a dimension to scan
+pub(crate) enum Area {
+    All,
+    Region(i32, i32),
+    // block radius around a block position
+    Radius { radius: i64, x: i64, z: i64 },
+}
+
+impl Area {
+    // `region <x> <z>`, `radius <blocks> <x> <z>` or nothing for the whole dimension
+    pub(crate) fn parse(args: &[&str]) -> Option<Self> {
+        match args {
+            [] => Some(Self::All),
+            ["region", x, z] => Some(Self::Region(x.parse().ok()?, z.parse().ok()?)),
+            ["radius", radius, x, z] => Some(Self::Radius {
+                radius: radius
+                    .parse::<i64>()
+                    .ok()?
+                    .checked_abs()
+                    .filter(|r| *r <= MAX_RADIUS)?,
+                x: coord(x)?,
+                z: coord(z)?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn regions(&self, world: &World, dim: Dimension) -> Vec<(i32, i32)> {
+        match self {
+            Self::All => world
+                .regions(dim)
+                .into_iter()
+                .map(|(x, z, _)| (x, z))
+                .collect(),
+            Self::Region(x, z) => vec![(*x, *z)],
+            Self::Radius { radius, x, z } => {
+                let (min_x, max_x) = ((x - radius).div_euclid(512), (x + radius).div_euclid(512));
+                let (min_z, max_z) = ((z - radius).div_euclid(512), (z + radius).div_euclid(512));
+                // only the regions that exist, a large radius covers a lot of empty space
+                world
+                    .regions(dim)
+                    .into_iter()
+                    .map(|(x, z, _)| (x, z))
+                    .filter(|(x, z)| {
+                        (min_x..=max_x).contains(&(*x as i64))
+                            && (min_z..=max_z).contains(&(*z as i64))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn contains(&self, pos: (i64, i64)) -> bool {
+        match self {
+            // positions come from the world and can be anything, too far away doesn't match
+            Self::Radius { radius, x, z } => {
+                let square = |d: Option<i64>| d.and_then(|d| d.checked_mul(d));
+                square(pos.0.checked_sub(*x))
+                    .zip(square(pos.1.checked_sub(*z)))
+                    .and_then(|(a, b)| a.checked_add(b))
+                    .is_some_and(|d| d <= radius * radius)
+            }
+            _ => true,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Found {
+    x: i64,
+    y: i64,
+    z: i64,
+    container: String,
+    count: i64,
+}
+
+// look through every container in the area for an item, including the contents of shulker
+// boxes and bundles inside them
+pub(crate) fn find_item(
+    world: World,
+    dim: Dimension,
+    item: &str,
+    area: Area,
+    progress: &Progress,
+) -> Result<Value, String> {
+    if !dim.folder(&world.path, "region").exists() {
+        return Err(format!("no {} region folder found", dim.name()));
+    }
+    let item = match item.contains(':') {
+        true => item.to_lowercase(),
+        false => format!("minecraft:{}", item.to_lowercase()),
+    };
+    let regions = area.regions(&world, dim);
+    let mut found = Vec::new();
+    for (i, (x, z)) in regions.iter().enumerate() {
+        progress.set(i, regions.len());
+        // block entities are stored with the chunk, entities in their own folder since 1.17
+        let paths = [
+            world.region_path(dim, *x, *z),
+            dim.folder(&world.path, "entities")
+                .join(format!("r.{x}.{z}.mca")),
+        ];
+        for path in paths {
+            let region = match Region::open(&path) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            for chunk in region.into_chunks() {
+                let nbt = &chunk.nbt;
+                for key in [
+                    &["block_entities"][..],
+                    &["Level", "TileEntities"],
+                    &["Level", "Entities"],
+                    &["Entities"],
+                ] {
+                    if let Some(list) = nbt.at(key).and_then(|l| l.as_list()) {
+                        search_containers(list, &item, &area, &mut found);
+                    }
+                }
+            }
+        }
+    }
+    progress.set(regions.len(), regions.len());
+    found.sort_by_key(|f| Reverse(f.count));
+    Ok(json!({
+        "item": item,
+        "dimension": dim.name(),
+        "regions": regions.len(),
+        "total": found.iter().fold(0i64, |t, f| t.saturating_add(f.count)),
+        "containers": found,
+    }))
+}
+
+// block entities have x, y and z, entities such as hopper minecarts a list of doubles
+fn search_containers(list: &[Tag], item: &str, area: &Area, found: &mut Vec<Found>) {
+    for container in list {
+        let items = match container.get("Items").and_then(|i| i.as_list()) {
+            Some(v) => v,
+            None => continue,
+        };
+        let pos = match container.get("Pos").and_then(|p| p.as_list()) {
+            Some(pos) => pos
+                .iter()
+                .filter_map(|p| p.as_f64())
+                .map(|p| p.floor() as i64)
+                .collect(),
+            None => ["x", "y", "z"]
+                .iter()
+                .filter_map(|k| container.get(k).and_then(|v| v.as_i64()))
+                .collect::<Vec<i64>>(),
+        };
+        if pos.len() != 3 || !area.contains((pos[0], pos[2])) {
+            continue;
+        }
+        let count = count_items(items, item, 0);
+        if count > 0 {
+            found.push(Found {
+                x: pos[0],
+                y: pos[1],
+                z: pos[2],
+                container: container
+                    .get("id")
+                    .and_then(|i| i.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                count,
+            });
+        }
+    }
+}
+
+fn count_items(items: &[Tag], item: &str, depth: usize) -> i64 {
+    if depth > MAX_NESTING {
+        return 0;
+    }
+    // counts come from the world and can be anything, saturate rather than overflow
+    let mut total: i64 = 0;
+    for stack in items {
+        // 1.20.5 moved the stack size to `count` and item nbt to components
+        let count = stack
+            .get("Count")
+            .or_else(|| stack.get("count"))
+            .and_then(|c| c.as_i64())
+            .unwrap_or(1);
+        if stack.get("id").and_then(|i| i.as_str()) == Some(item) {
+            total = total.saturating_add(count);
+        }
+        let nested = [
+            stack.at(&["tag", "BlockEntityTag", "Items"]),
+            stack.at(&["tag", "Items"]),
+            stack.at(&["components", "minecraft:bundle_contents"]),
+        ];
+        for contents in nested.into_iter().flatten() {
+            if let Some(v) = contents.as_list() {
+                total = total.saturating_add(count.saturating_mul(count_items(v, item, depth + 1)));
+            }
+        }
+        // container components wrap each stack with its slot
+        if let Some(v) = stack
+            .at(&["components", "minecraft:container"])
+            .and_then(|c| c.as_list())
+        {
+            let stacks: Vec<Tag> = v.iter().filter_map(|s| s.get("item")).cloned().collect();
+            total =
+                total.saturating_add(count.saturating_mul(count_items(&stacks, item, depth + 1)));
+        }
+    }
+    total
+}
@@ -0,0 +1,48 @@
+use crate::nbt;
+use serde_json::{json, Value};
+use std::{cmp::Reverse, collections::HashMap};
+
+// what a structure block file contains, so builders can see what it needs before pasting it
+pub(crate) fn summary(data: &[u8]) -> Result<Value, String> {
+    let root = nbt::read_compressed(data).map_err(|e| format!("invalid structure: {e}"))?;
+    // structures saved with several palettes (e.g. shipwrecks) use the first one
+    let palette = root
+        .get("palette")
+        .or_else(|| root.get("palettes").and_then(|p| p.as_list()?.first()))
+        .and_then(|p| p.as_list())
+        .unwrap_or_default();
+    let names: Vec<&str> = palette
+        .iter()
+        .map(|b| b.get("Name").and_then(|n| n.as_str()).unwrap_or_default())
+        .collect();
+    let mut materials: HashMap<&str, u64> = HashMap::new();
+    let blocks = root
+        .get("blocks")
+        .and_then(|b| b.as_list())
+        .unwrap_or_default();
+    for block in blocks {
+        let name = block
+            .get("state")
+            .and_then(|s| s.as_i64())
+            .and_then(|s| names.get(s as usize));
+        match name {
+            Some(&"minecraft:air") | None => continue,
+            Some(name) => *materials.entry(name).or_default() += 1,
+        }
+    }
+    let mut materials: Vec<(&str, u64)> = materials.into_iter().collect();
+    materials.sort_by_key(|(name, count)| (Reverse(*count), *name));
+    Ok(json!({
+        "size": root.get("size").and_then(|s| s.as_ints()),
+        "blocks": materials.iter().map(|(_, c)| c).sum::<u64>(),
+        "entities": root
+            .get("entities")
+            .and_then(|e| e.as_list())
+            .map_or(0, |e| e.len()),
+        "data_version": root.get("DataVersion").and_then(|v| v.as_i64()),
+        "palette": materials
+            .iter()
+            .map(|(name, count)| json!({ "block": name, "count": count }))
+            .collect::<Vec<Value>>(),
+    }))
+}
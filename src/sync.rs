@@ -0,0 +1,160 @@
+use crate::{
+    jobs::Progress,
+    region::{pack, write_region, ChunkEntry, Dimension, Region, World, CHUNK_FOLDERS},
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// regions one SYNC_REGIONS can copy, 16 by 16 regions is 8192 blocks across
+pub(crate) const MAX_REGIONS: i64 = 256;
+
+// an area of chunks, inclusive on both ends
+#[derive(Clone, Copy)]
+pub(crate) struct ChunkArea {
+    x1: i32,
+    z1: i32,
+    x2: i32,
+    z2: i32,
+}
+
+impl ChunkArea {
+    // `<x1> <z1> <x2> <z2>` in block coordinates, the corners can be given in any order
+    pub(crate) fn parse(args: &[&str]) -> Option<Self> {
+        let coords: Vec<i32> = args.iter().map(|a| a.parse().ok()).collect::<Option<_>>()?;
+        let [x1, z1, x2, z2] = coords[..] else {
+            return None;
+        };
+        Some(Self {
+            x1: x1.min(x2).div_euclid(16),
+            z1: z1.min(z2).div_euclid(16),
+            x2: x1.max(x2).div_euclid(16),
+            z2: z1.max(z2).div_euclid(16),
+        })
+    }
+
+    fn contains(&self, x: i32, z: i32) -> bool {
+        (self.x1..=self.x2).contains(&x) && (self.z1..=self.z2).contains(&z)
+    }
+
+    pub(crate) fn region_count(&self) -> i64 {
+        let width = (self.x2.div_euclid(32) - self.x1.div_euclid(32)) as i64 + 1;
+        let height = (self.z2.div_euclid(32) - self.z1.div_euclid(32)) as i64 + 1;
+        width * height
+    }
+
+    // region coordinates that overlap the area
+    fn regions(&self) -> Vec<(i32, i32)> {
+        (self.x1.div_euclid(32)..=self.x2.div_euclid(32))
+            .flat_map(|x| (self.z1.div_euclid(32)..=self.z2.div_euclid(32)).map(move |z| (x, z)))
+            .collect()
+    }
+}
+
+// a missing region file is the same as one without chunks
+fn open_or_empty(path: &Path) -> Result<Region, String> {
+    match path.exists() {
+        true => Region::open(path),
+        false => Region::from_data(path, Vec::new()),
+    }
+    .map_err(|e| format!("unable to read {}: {e}", path.display()))
+}
+
+fn external(dir: &Path, entry: &ChunkEntry) -> PathBuf {
+    dir.join(format!("c.{}.{}.mcc", entry.x, entry.z))
+}
+
+// make the area of `dst` match `src`, chunks missing in `src` are removed from `dst` as well.
+// chunks outside the area are left as they are
+pub(crate) fn sync_regions(
+    src: &World,
+    dst: &World,
+    dim: Dimension,
+    area: ChunkArea,
+    progress: &Progress,
+) -> Result<Value, String> {
+    if !dim.folder(&src.path, "region").exists() {
+        return Err(format!("no {} region folder found", dim.name()));
+    }
+    let regions = area.regions();
+    let mut changed = Vec::new();
+    let (mut copied, mut removed) = (0, 0);
+    for (i, (x, z)) in regions.iter().enumerate() {
+        progress.set(i, regions.len());
+        let name = format!("r.{x}.{z}.mca");
+        for folder in CHUNK_FOLDERS {
+            let (src_dir, dst_dir) = (dim.folder(&src.path, folder), dim.folder(&dst.path, folder));
+            let from = open_or_empty(&src_dir.join(&name))?;
+            let to = open_or_empty(&dst_dir.join(&name))?;
+            // slot index to the region the chunk is taken from
+            let mut chunks: HashMap<usize, (&Region, ChunkEntry)> = HashMap::new();
+            for entry in to.entries() {
+                if !area.contains(entry.x, entry.z) {
+                    chunks.insert(entry.index, (&to, entry));
+                }
+            }
+            let synced: Vec<ChunkEntry> = from
+                .entries()
+                .into_iter()
+                .filter(|e| area.contains(e.x, e.z))
+                .collect();
+            for entry in &synced {
+                chunks.insert(entry.index, (&from, *entry));
+            }
+            let dropped = to
+                .entries()
+                .into_iter()
+                .filter(|e| area.contains(e.x, e.z) && !chunks.contains_key(&e.index))
+                .count();
+            let mut chunks: Vec<(&Region, ChunkEntry)> = chunks.into_values().collect();
+            chunks.sort_by_key(|(_, e)| e.index);
+            let data = pack(&chunks);
+            // same chunks at the same timestamps, nothing to write
+            if data
+                == pack(
+                    &to.entries()
+                        .into_iter()
+                        .map(|e| (&to, e))
+                        .collect::<Vec<_>>(),
+                )
+            {
+                continue;
+            }
+            let existed = to.path.exists();
+            fs::create_dir_all(&dst_dir).map_err(|e| format!("unable to create folder: {e}"))?;
+            write_region(&to.path, &data)
+                .map_err(|e| format!("failed to write {}: {e}", to.path.display()))?;
+            // chunks too big for a region are kept in their own file next to it
+            for entry in to.entries().iter().filter(|e| area.contains(e.x, e.z)) {
+                let _ = fs::remove_file(external(&dst_dir, entry));
+            }
+            for entry in &synced {
+                let file = external(&src_dir, entry);
+                if file.exists() {
+                    fs::copy(&file, external(&dst_dir, entry))
+                        .map_err(|e| format!("failed to copy {}: {e}", file.display()))?;
+                }
+            }
+            copied += synced.len();
+            removed += dropped;
+            changed.push(json!({
+                "file": dim.folder(Path::new(""), folder).join(&name),
+                "chunks": synced.len(),
+                "removed": dropped,
+                "created": !existed,
+                "deleted": data.is_empty(),
+            }));
+        }
+    }
+    progress.set(regions.len(), regions.len());
+    Ok(json!({
+        "dimension": dim.name(),
+        "regions": regions.len(),
+        "chunks_copied": copied,
+        "chunks_removed": removed,
+        "changed": changed,
+    }))
+}
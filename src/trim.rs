@@ -0,0 +1,139 @@
+use crate::{
+    jobs::Progress,
+    region::{write_region, Dimension, Region, World, CHUNK_FOLDERS},
+    utils::unix_millis,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub(crate) struct TrimOptions {
+    // ticks players have spent near a chunk, summed over all players
+    pub min_inhabited: i64,
+    // chunks saved more recently than this many seconds ago are kept regardless
+    pub min_age: Option<u64>,
+    pub dry_run: bool,
+}
+
+impl TrimOptions {
+    // `--min-inhabited <ticks> [--min-age <days>] [--dry-run]`
+    pub(crate) fn parse(args: &[&str]) -> Option<Self> {
+        let mut options = Self {
+            min_inhabited: -1,
+            min_age: None,
+            dry_run: false,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match *arg {
+                "--min-inhabited" => options.min_inhabited = args.next()?.parse().ok()?,
+                "--min-age" => options.min_age = Some(args.next()?.parse::<u64>().ok()? * 86400),
+                "--dry-run" => options.dry_run = true,
+                _ => return None,
+            }
+        }
+        if options.min_inhabited < 0 {
+            return None;
+        }
+        Some(options)
+    }
+}
+
+// drop chunks players barely spent time in and compact the region files, the server has to be
+// stopped so it doesn't write the chunks back
+pub(crate) fn trim_world(
+    world: &World,
+    dim: Dimension,
+    options: &TrimOptions,
+    progress: &Progress,
+) -> Result<Value, String> {
+    if !dim.folder(&world.path, "region").exists() {
+        return Err(format!("no {} region folder found", dim.name()));
+    }
+    let now = unix_millis() / 1000;
+    let regions = world.regions(dim);
+    let (mut chunks, mut removed, mut regions_removed, mut saved) = (0, 0, 0, 0);
+    for (i, (x, z, path)) in regions.iter().enumerate() {
+        progress.set(i, regions.len());
+        let region = match Region::open(path) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let mut remove = HashSet::new();
+        for entry in region.entries() {
+            chunks += 1;
+            // anything that can't be read is left alone
+            let chunk = match region.read(&entry) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let inhabited = chunk
+                .nbt
+                .get("InhabitedTime")
+                .or_else(|| chunk.nbt.at(&["Level", "InhabitedTime"]))
+                .and_then(|t| t.as_i64());
+            let old_enough = options
+                .min_age
+                .is_none_or(|age| now.saturating_sub(chunk.timestamp as u64) >= age);
+            if inhabited.is_some_and(|t| t < options.min_inhabited) && old_enough {
+                remove.insert(entry.index);
+            }
+        }
+        if remove.is_empty() {
+            continue;
+        }
+        removed += remove.len();
+        let name = format!("r.{x}.{z}.mca");
+        // a trimmed chunk is removed from every folder
+        for folder in CHUNK_FOLDERS {
+            let dir = dim.folder(&world.path, folder);
+            let region = match Region::open(dir.join(&name)) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let external = external_chunks(&region, &remove, &dir);
+            let data = region.without(&remove);
+            saved += region.len().saturating_sub(data.len());
+            saved += external
+                .iter()
+                .filter_map(|f| fs::metadata(f).ok())
+                .map(|m| m.len() as usize)
+                .sum::<usize>();
+            if folder == "region" && data.is_empty() {
+                regions_removed += 1;
+            }
+            if options.dry_run {
+                continue;
+            }
+            write_region(&region.path, &data)
+                .map_err(|e| format!("failed to write {}: {e}", region.path.display()))?;
+            for file in external {
+                let _ = fs::remove_file(file);
+            }
+        }
+    }
+    progress.set(regions.len(), regions.len());
+    Ok(json!({
+        "dimension": dim.name(),
+        "dry_run": options.dry_run,
+        "regions": regions.len(),
+        "chunks": chunks,
+        "chunks_removed": removed,
+        "regions_removed": regions_removed,
+        "bytes_saved": saved,
+    }))
+}
+
+// chunks too big for the region file are kept next to it as `c.<x>.<z>.mcc`
+fn external_chunks(region: &Region, remove: &HashSet<usize>, dir: &Path) -> Vec<PathBuf> {
+    region
+        .entries()
+        .into_iter()
+        .filter(|e| remove.contains(&e.index))
+        .map(|e| dir.join(format!("c.{}.{}.mcc", e.x, e.z)))
+        .filter(|f| f.exists())
+        .collect()
+}
@@ -0,0 +1,296 @@
+use crate::{
+    bridge::ServerState,
+    nbt,
+    region::{parse_region_name, Dimension, Region, World},
+    utils::{unix_millis, Result},
+    ws::{check_password, CONFIG, SESSIONS},
+};
+use log::info;
+use serde_json::{json, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use warp::{
+    http::StatusCode,
+    hyper::body::Bytes,
+    path::Tail,
+    reply::{self, Reply},
+};
+
+// big enough for any region the game writes, chunks over 1MB live in their own .mcc file
+pub(crate) const MAX_UPLOAD: u64 = 256 * 1024 * 1024;
+// what an upload replaced is kept in `<backup_location>/undo/<session>/<id>/`
+const UNDO_FOLDER: &str = "undo";
+const UNDO_FILE: &str = "upload.json";
+
+enum Upload {
+    // region, entities or poi file of a dimension
+    Region {
+        kind: String,
+        dim: Dimension,
+        name: String,
+    },
+    Structure(String),
+}
+
+impl Upload {
+    // `<region/entities/poi>/<OW/NETHER/END>/r.<x>.<z>.mca` or `structure/<name>.nbt`
+    fn parse(path: &[&str]) -> Option<Self> {
+        match path {
+            [kind @ ("region" | "entities" | "poi"), dim, name] => {
+                parse_region_name(name)?;
+                Some(Self::Region {
+                    kind: kind.to_string(),
+                    dim: Dimension::parse(dim)?,
+                    name: name.to_string(),
+                })
+            }
+            ["structure", name] => {
+                let stem = name.strip_suffix(".nbt")?;
+                if stem.is_empty() || stem.starts_with('.') {
+                    return None;
+                }
+                Some(Self::Structure(name.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    // relative to the world folder
+    fn file(&self) -> PathBuf {
+        match self {
+            Self::Region { kind, dim, name } => dim.folder(Path::new(""), kind).join(name),
+            Self::Structure(name) => Path::new("structure").join(name),
+        }
+    }
+}
+
+// DataVersion the world was last saved with, anything newer can't be loaded by the server
+fn world_version(world: &World) -> std::result::Result<i64, String> {
+    let data = fs::read(world.path.join("level.dat"))
+        .map_err(|e| format!("unable to read level.dat: {e}"))?;
+    nbt::read_compressed(&data)
+        .map_err(|e| format!("invalid level.dat: {e}"))?
+        .at(&["Data", "DataVersion"])
+        .and_then(|v| v.as_i64())
+        .ok_or_else(|| "level.dat has no DataVersion".to_owned())
+}
+
+fn check_version(found: Option<i64>, world: i64, what: &str) -> std::result::Result<(), String> {
+    match found {
+        Some(v) if v > world => Err(format!(
+            "{what} has DataVersion {v} but the world is on {world}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+// every chunk has to decompress, parse and sit where the file name says it does
+fn validate_region(path: &Path, data: Vec<u8>, version: i64) -> std::result::Result<usize, String> {
+    if data.is_empty() {
+        return Err("region is empty".to_owned());
+    }
+    let region = Region::from_data(path, data).map_err(|e| format!("invalid region: {e}"))?;
+    let entries = region.entries();
+    for entry in &entries {
+        let (compression, _) = region
+            .raw(entry)
+            .map_err(|e| format!("chunk {} {}: {e}", entry.x, entry.z))?;
+        // the matching .mcc file isn't part of the upload
+        if compression & 128 != 0 {
+            return Err(format!(
+                "chunk {} {} is stored outside the region file",
+                entry.x, entry.z
+            ));
+        }
+        let chunk = region
+            .read(entry)
+            .map_err(|e| format!("chunk {} {}: {e}", entry.x, entry.z))?;
+        let pos = |key: &str| {
+            chunk
+                .nbt
+                .get(key)
+                .or_else(|| chunk.nbt.at(&["Level", key]))
+                .and_then(|v| v.as_i64())
+        };
+        // entities and poi chunks store their position as `Position` or not at all
+        let stored = match (pos("xPos"), pos("zPos")) {
+            (Some(x), Some(z)) => Some((x, z)),
+            _ => chunk
+                .nbt
+                .get("Position")
+                .and_then(|p| p.as_ints())
+                .filter(|p| p.len() == 2)
+                .map(|p| (p[0], p[1])),
+        };
+        if stored.is_some_and(|p| p != (entry.x as i64, entry.z as i64)) {
+            return Err(format!(
+                "chunk {} {} belongs to a different region",
+                entry.x, entry.z
+            ));
+        }
+        check_version(
+            chunk.nbt.get("DataVersion").and_then(|v| v.as_i64()),
+            version,
+            &format!("chunk {} {}", entry.x, entry.z),
+        )?;
+    }
+    Ok(entries.len())
+}
+
+fn validate_structure(data: &[u8], version: i64) -> std::result::Result<(), String> {
+    let root = nbt::read_compressed(data).map_err(|e| format!("invalid structure: {e}"))?;
+    if root.get("size").is_none() || root.get("blocks").is_none() {
+        return Err("invalid structure: missing size or blocks".to_owned());
+    }
+    check_version(
+        root.get("DataVersion").and_then(|v| v.as_i64()),
+        version,
+        "structure",
+    )
+}
+
+// validate an upload and put it into the world, the file it replaces is kept for UNDO_UPLOAD
+fn place(
+    world: &World,
+    session: &str,
+    upload: &Upload,
+    data: Vec<u8>,
+    backup_location: &str,
+) -> std::result::Result<Value, String> {
+    let version = world_version(world)?;
+    let file = upload.file();
+    let target = world.path.join(&file);
+    let chunks = match upload {
+        Upload::Region { .. } => Some(validate_region(&target, data.clone(), version)?),
+        Upload::Structure(_) => {
+            validate_structure(&data, version)?;
+            None
+        }
+    };
+    let id = unix_millis().to_string();
+    let undo = Path::new(backup_location)
+        .join(UNDO_FOLDER)
+        .join(session)
+        .join(&id);
+    fs::create_dir_all(&undo).map_err(|e| format!("unable to create undo folder: {e}"))?;
+    let existed = target.exists();
+    if existed {
+        fs::copy(&target, undo.join(file.file_name().unwrap_or_default()))
+            .map_err(|e| format!("unable to keep undo copy: {e}"))?;
+    }
+    let record = json!({ "file": file, "existed": existed });
+    fs::write(undo.join(UNDO_FILE), record.to_string())
+        .map_err(|e| format!("unable to write undo record: {e}"))?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("unable to create folder: {e}"))?;
+    }
+    // written next to the target and renamed so the world never sees half a file
+    let temp = target.with_extension("upload.tmp");
+    fs::write(&temp, &data)
+        .and_then(|_| fs::rename(&temp, &target))
+        .map_err(|e| format!("unable to write {}: {e}", target.display()))?;
+    info!("uploaded {} into {session}", file.display());
+    Ok(json!({
+        "session": session,
+        "file": file,
+        "bytes": data.len(),
+        "chunks": chunks,
+        "replaced": existed,
+        "undo": id,
+    }))
+}
+
+// put back what an upload replaced, or remove the file if it was new
+pub(crate) fn undo(
+    world: &World,
+    session: &str,
+    id: &str,
+    backup_location: &str,
+) -> std::result::Result<String, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return Err("Invalid Upload ID".to_owned());
+    }
+    let undo = Path::new(backup_location)
+        .join(UNDO_FOLDER)
+        .join(session)
+        .join(id);
+    let record: Value = fs::read_to_string(undo.join(UNDO_FILE))
+        .ok()
+        .and_then(|r| serde_json::from_str(&r).ok())
+        .ok_or_else(|| "Upload not found".to_owned())?;
+    let file = match record["file"].as_str() {
+        Some(v) => PathBuf::from(v),
+        None => return Err("Invalid undo record".to_owned()),
+    };
+    let target = world.path.join(&file);
+    let (result, done) = match record["existed"].as_bool() == Some(true) {
+        true => (
+            fs::copy(undo.join(file.file_name().unwrap_or_default()), &target).map(|_| ()),
+            "restored",
+        ),
+        false => (fs::remove_file(&target), "removed"),
+    };
+    result.map_err(|e| format!("failed to restore {}: {e}", file.display()))?;
+    let _ = fs::remove_dir_all(undo);
+    Ok(format!("{done} {}", file.display()))
+}
+
+fn respond(status: StatusCode, body: String) -> Result<reply::Response> {
+    Ok(reply::with_status(body, status).into_response())
+}
+
+// PUT /taurus/upload/<session>/<path>, authenticated with the websocket password
+pub(crate) async fn upload_handler(
+    tail: Tail,
+    auth: Option<String>,
+    body: Bytes,
+) -> Result<reply::Response> {
+    let password = auth.as_deref().unwrap_or_default();
+    if !check_password(password.strip_prefix("Bearer ").unwrap_or(password)).await {
+        return respond(StatusCode::UNAUTHORIZED, "Invalid Password".into());
+    }
+    let path: Vec<&str> = tail.as_str().split('/').collect();
+    let (session, upload) = match path.split_first() {
+        Some((session, rest)) => match Upload::parse(rest) {
+            Some(v) => (session.to_string(), v),
+            None => return respond(StatusCode::BAD_REQUEST, "Invalid Upload Path".into()),
+        },
+        None => return respond(StatusCode::BAD_REQUEST, "Invalid Upload Path".into()),
+    };
+    let target = SESSIONS
+        .read()
+        .await
+        .iter()
+        .find(|s| s.name == session)
+        .cloned();
+    let target = match target {
+        Some(v) if v.game.is_some() => v,
+        _ => return respond(StatusCode::NOT_FOUND, "Invalid Session Target".into()),
+    };
+    let world = match target.game.as_ref().and_then(|g| g.world()) {
+        Some(v) => v,
+        None => return respond(StatusCode::NOT_FOUND, "no configured file path".into()),
+    };
+    // the server would overwrite the region with the chunks it has loaded
+    if matches!(upload, Upload::Region { .. })
+        && target.server_state().await != ServerState::Stopped
+    {
+        return respond(
+            StatusCode::CONFLICT,
+            "Stop the server before uploading regions".into(),
+        );
+    }
+    let backup_location = CONFIG.read().await.backup_location.clone();
+    let data = body.to_vec();
+    let placed = tokio::task::spawn_blocking(move || {
+        place(&world, &session, &upload, data, &backup_location)
+    })
+    .await;
+    match placed {
+        Ok(Ok(v)) => respond(StatusCode::OK, v.to_string()),
+        Ok(Err(e)) => respond(StatusCode::UNPROCESSABLE_ENTITY, e),
+        Err(e) => respond(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
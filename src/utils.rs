@@ -1,6 +1,7 @@
 use serde::{Serialize, Serializer};
 use serde_derive::Serialize;
 use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{collections::HashMap, sync::Arc};
 use sysinfo::{DiskExt, System, SystemExt};
 use tokio::sync::{mpsc, Mutex};
@@ -16,6 +17,13 @@ macro_rules! exit {
     };
 }
 
+pub(crate) fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
 pub(crate) struct WsClient {
     pub sender: Option<mpsc::UnboundedSender<std::result::Result<Message, warp::Error>>>,
     pub authed: bool,
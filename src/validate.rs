@@ -0,0 +1,485 @@
+use crate::{
+    backup::Game,
+    bridge::Session,
+    config::Config,
+    overrides::{self, Env, Sources},
+    parser::LogFlavor,
+};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+};
+
+// the main config can be written in either format, toml wins if both exist
+pub(crate) const CONFIG_FILES: [&str; 2] = ["config.toml", "config.json"];
+
+// a problem with a config file, pointing at the line and field where possible
+pub(crate) struct ConfigError {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, ": {field}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+// a parsed config file along with its text, so later checks can point at lines
+pub(crate) struct Loaded<T> {
+    pub file: PathBuf,
+    pub data: String,
+    pub sources: Sources,
+    pub value: T,
+}
+
+fn is_toml(file: &Path) -> bool {
+    file.extension().is_some_and(|e| e == "toml")
+}
+
+// line of the last key of a field path such as `game.log_pattern` or `routes[1].filter`, each
+// key is searched for after the previous one
+fn line_of(data: &str, field: &str, toml: bool) -> Option<usize> {
+    let mut offset = 0;
+    let mut found = None;
+    for key in field
+        .split(['.', '[', ']'])
+        .filter(|k| !k.is_empty() && !k.chars().all(|c| c.is_ascii_digit()))
+    {
+        let key = regex::escape(key);
+        let pattern = match toml {
+            true => format!(r#"(?m)^\s*(\[\[?([^\]]*\.)?"?{key}"?\]\]?|"?{key}"?\s*=)"#),
+            false => format!(r#""{key}"\s*:"#),
+        };
+        if let Some(m) = Regex::new(&pattern).ok()?.find_at(data, offset) {
+            offset = m.end();
+            found = Some(data[..m.start()].matches('\n').count() + 1);
+        }
+    }
+    found
+}
+
+fn field_name(path: &serde_path_to_error::Path) -> Option<String> {
+    Some(path.to_string()).filter(|p| p != ".")
+}
+
+// serde errors end with their own position and field, which are reported separately
+fn strip_position(message: String) -> String {
+    match message
+        .find(" for key `")
+        .or_else(|| message.find(" at line "))
+    {
+        Some(v) => message[..v].to_owned(),
+        None => message,
+    }
+}
+
+fn error(file: &Path, line: Option<usize>, field: Option<String>, message: String) -> ConfigError {
+    ConfigError {
+        file: file.to_path_buf(),
+        line,
+        field,
+        message,
+    }
+}
+
+// syntax errors are reported here, anything about the fields themselves once overrides are applied
+fn parse_value(file: &Path, data: &str) -> Result<Value, ConfigError> {
+    match is_toml(file) {
+        true => toml::from_str(data).map_err(|e| {
+            let line = e.line_col().map(|(l, _)| l + 1);
+            error(file, line, None, strip_position(e.to_string()))
+        }),
+        false => serde_json::from_str(data)
+            .map_err(|e| error(file, Some(e.line()), None, strip_position(e.to_string()))),
+    }
+}
+
+// fields set by the environment or a secret reference have no line, the error says where they
+// came from instead
+fn located(
+    file: &Path,
+    data: &str,
+    sources: &Sources,
+    field: String,
+    message: String,
+) -> ConfigError {
+    match sources.get(&field) {
+        Some(source) => error(
+            file,
+            None,
+            Some(field),
+            format!("{message} (from {source})"),
+        ),
+        None => error(
+            file,
+            line_of(data, &field, is_toml(file)),
+            Some(field),
+            message,
+        ),
+    }
+}
+
+fn read<T: DeserializeOwned>(
+    file: &Path,
+    session: bool,
+    env: &Env,
+) -> Result<Loaded<T>, ConfigError> {
+    let data = fs::read_to_string(file)
+        .map_err(|e| error(file, None, None, format!("unable to read file: {e}")))?;
+    let mut value = parse_value(file, &data)?;
+    let (sources, mut guessed) = overrides::apply(&mut value, session, env)
+        .map_err(|(field, message)| located(file, &data, &Sources::new(), field, message))?;
+    // overrides are read as json, a password like 1234 becomes a number and is retried as text
+    let value = loop {
+        let failed = match serde_path_to_error::deserialize(value.clone()) {
+            Ok(v) => break v,
+            Err(e) => e,
+        };
+        let field = field_name(failed.path());
+        let message = strip_position(failed.into_inner().to_string());
+        if let Some(f) = &field {
+            if message.starts_with("invalid type")
+                && overrides::as_string(&mut value, &mut guessed, f)
+            {
+                continue;
+            }
+        }
+        return Err(match field {
+            Some(field) => located(file, &data, &sources, field, message),
+            None => error(file, None, None, message),
+        });
+    };
+    Ok(Loaded {
+        file: file.to_path_buf(),
+        data,
+        sources,
+        value,
+    })
+}
+
+pub(crate) fn config_file(path: &Path) -> Option<PathBuf> {
+    CONFIG_FILES
+        .iter()
+        .map(|f| path.join(f))
+        .find(|f| f.exists())
+}
+
+pub(crate) fn load_config(path: &Path) -> Result<Loaded<Config>, ConfigError> {
+    match config_file(path) {
+        Some(file) => read(&file, false, &overrides::environment()),
+        None => Err(ConfigError {
+            file: path.join(CONFIG_FILES[0]),
+            line: None,
+            field: None,
+            message: "no config file found".to_owned(),
+        }),
+    }
+}
+
+// every .json and .toml file in the servers folder that parses, along with the errors of the
+// ones that don't
+pub(crate) fn load_sessions(path: &Path) -> (Vec<Loaded<Session>>, Vec<ConfigError>) {
+    let dir = path.join("servers");
+    let mut files: Vec<PathBuf> = match fs::read_dir(&dir) {
+        Ok(v) => v
+            .flatten()
+            .map(|f| f.path())
+            .filter(|f| f.extension().is_some_and(|e| e == "json" || e == "toml"))
+            .collect(),
+        Err(e) => {
+            let error = ConfigError {
+                file: dir,
+                line: None,
+                field: None,
+                message: format!("unable to read server directory: {e}"),
+            };
+            return (Vec::new(), vec![error]);
+        }
+    };
+    files.sort();
+    let env = overrides::environment();
+    let mut sessions = Vec::new();
+    let mut errors = Vec::new();
+    for file in files {
+        match read(&file, true, &env) {
+            Ok(v) => sessions.push(v),
+            Err(e) => errors.push(e),
+        }
+    }
+    (sessions, errors)
+}
+
+struct Checker<'a, T> {
+    loaded: &'a Loaded<T>,
+    errors: &'a mut Vec<ConfigError>,
+}
+
+impl<T> Checker<'_, T> {
+    fn error<M: Into<String>>(&mut self, field: &str, message: M) {
+        self.errors.push(located(
+            &self.loaded.file,
+            &self.loaded.data,
+            &self.loaded.sources,
+            field.to_owned(),
+            message.into(),
+        ));
+    }
+
+    fn regex(&mut self, field: &str, pattern: Option<&String>) {
+        // regex errors draw the pattern over several lines, the last one says what's wrong
+        if let Some(Err(e)) = pattern.map(|p| Regex::new(p)) {
+            let e = e.to_string();
+            let reason = e.lines().last().unwrap_or_default();
+            self.error(
+                field,
+                format!("invalid regex: {}", reason.trim_start_matches("error: ")),
+            );
+        }
+    }
+}
+
+fn check_config(
+    loaded: &Loaded<Config>,
+    sessions: Option<&[String]>,
+    errors: &mut Vec<ConfigError>,
+) {
+    let config = &loaded.value;
+    let mut c = Checker { loaded, errors };
+    if config.ws_ip.parse::<Ipv4Addr>().is_err() {
+        c.error(
+            "ws_ip",
+            format!("`{}` is not an ipv4 address", config.ws_ip),
+        );
+    }
+    if config.ws_port == 0 || config.ws_port > u16::MAX as u64 {
+        c.error("ws_port", format!("{} is not a valid port", config.ws_port));
+    }
+    if config.ws_password.is_empty() {
+        c.error("ws_password", "must not be empty, anyone could connect");
+    }
+    if config.backup_location.trim().is_empty() {
+        c.error("backup_location", "must not be empty");
+    }
+    if config.webserver_location.is_some() != config.webserver_prefix.is_some() {
+        c.error(
+            "webserver_location",
+            "webserver_location and webserver_prefix have to be set together",
+        );
+    }
+    if let Some(v) = config.restart_script.as_deref().filter(|s| !s.is_empty()) {
+        if !Path::new(v).exists() {
+            c.error("restart_script", format!("{v} does not exist"));
+        }
+    }
+    if let Some(v) = &config.public_url {
+        if !v.starts_with("http://") && !v.starts_with("https://") {
+            c.error("public_url", "has to start with http:// or https://");
+        }
+    }
+    if config.download_ttl == Some(0) {
+        c.error("download_ttl", "must be more than 0");
+    }
+    if config.history_size == Some(0) {
+        c.error("history_size", "must be more than 0");
+    }
+    for (i, script) in config.scripts.iter().flatten().enumerate() {
+        if script
+            .shell_cmd
+            .as_deref()
+            .is_some_and(|s| s.trim().is_empty())
+        {
+            c.error(&format!("scripts[{i}].shell_cmd"), "must not be empty");
+        }
+        match (&script.rcon_cmd, &script.session_name, sessions) {
+            (Some(_), None, _) => {
+                c.error(&format!("scripts[{i}].rcon_cmd"), "needs a session_name");
+            }
+            (_, Some(name), Some(sessions)) if !sessions.contains(name) => {
+                c.error(
+                    &format!("scripts[{i}].session_name"),
+                    format!("no session named {name}"),
+                );
+            }
+            _ => {}
+        }
+    }
+    for (i, route) in config.routes.iter().flatten().enumerate() {
+        if route.to.is_empty() {
+            c.error(&format!("routes[{i}].to"), "must name at least one target");
+        }
+        c.regex(&format!("routes[{i}].filter"), route.filter.as_ref());
+        c.regex(&format!("routes[{i}].exclude"), route.exclude.as_ref());
+    }
+    for (name, members) in config.channels.iter().flatten() {
+        match (members.is_empty(), sessions) {
+            (true, _) => c.error(&format!("channels.{name}"), "has no sessions"),
+            (false, Some(sessions)) => {
+                for member in members.iter().filter(|m| !sessions.contains(m)) {
+                    c.error(
+                        &format!("channels.{name}"),
+                        format!("no session named {member}"),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_game(c: &mut Checker<Session>, game: &Game) {
+    if game
+        .file_path
+        .as_deref()
+        .is_some_and(|p| p.trim().is_empty())
+    {
+        c.error("game.file_path", "must not be empty");
+    }
+    // these are used with %, so 0 would crash the scheduler
+    if game.backup_interval == Some(0) {
+        c.error("game.backup_interval", "must be more than 0");
+    }
+    if game.cmd_prefix.as_deref() == Some("") {
+        c.error("game.cmd_prefix", "must not be empty");
+    }
+    c.regex("game.log_pattern", game.log_pattern.as_ref());
+    if matches!(game.log_flavor, Some(LogFlavor::Custom)) && game.log_pattern.is_none() {
+        c.error("game.log_flavor", "the custom flavor needs a log_pattern");
+    }
+    for (name, rule) in game.commands.iter().flatten() {
+        if rule.reply.is_some() && rule.ws.is_some() {
+            c.error(
+                &format!("game.commands.{name}"),
+                "can either reply or run a websocket command, not both",
+            );
+        }
+    }
+}
+
+fn check_session(loaded: &Loaded<Session>, errors: &mut Vec<ConfigError>) {
+    let session = &loaded.value;
+    let mut c = Checker { loaded, errors };
+    if session.name.trim().is_empty() {
+        c.error("name", "must not be empty");
+    }
+    if session.name.contains(char::is_whitespace) {
+        c.error("name", "can't contain spaces, commands take it as one word");
+    }
+    if let Some(rcon) = &session.rcon {
+        if rcon.port == 0 {
+            c.error("rcon.port", "0 is not a valid port");
+        }
+        if rcon.password.is_empty() {
+            c.error("rcon.password", "must not be empty");
+        }
+        if rcon.timeout == Some(0) {
+            c.error("rcon.timeout", "must be more than 0");
+        }
+    }
+    if let Some(game) = &session.game {
+        check_game(&mut c, game);
+    }
+}
+
+// everything wrong with the config and sessions, sessions are checked against each other and the
+// config when both are given
+pub(crate) fn check(
+    config: Option<&Loaded<Config>>,
+    sessions: Option<&[Loaded<Session>]>,
+) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    let names: Option<Vec<String>> =
+        sessions.map(|s| s.iter().map(|l| l.value.name.clone()).collect());
+    if let Some(config) = config {
+        check_config(config, names.as_deref(), &mut errors);
+    }
+    let mut seen: HashMap<&str, &Path> = HashMap::new();
+    for loaded in sessions.unwrap_or_default() {
+        check_session(loaded, &mut errors);
+        if let Some(first) = seen.insert(&loaded.value.name, &loaded.file) {
+            let mut c = Checker {
+                loaded,
+                errors: &mut errors,
+            };
+            c.error(
+                "name",
+                format!(
+                    "{} is already used in {}",
+                    loaded.value.name,
+                    first.display()
+                ),
+            );
+        }
+    }
+    errors
+}
+
+// everything `taurus check` finds, files that don't parse are reported along with the problems
+// in the ones that do
+pub(crate) fn check_all(path: &Path) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    let config = match load_config(path) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            errors.push(e);
+            None
+        }
+    };
+    let (sessions, session_errors) = load_sessions(path);
+    errors.extend(session_errors);
+    errors.extend(check(config.as_ref(), Some(&sessions)));
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn digit_passwords_stay_strings() {
+        let dir = env::temp_dir().join(format!("taurus-validate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config.toml");
+        let session = dir.join("digits.toml");
+        fs::write(
+            &config,
+            "ws_ip = \"127.0.0.1\"\nbackup_location = \"/tmp\"\n",
+        )
+        .unwrap();
+        fs::write(&session, "name = \"digits\"\nhost = \"tmux\"\n").unwrap();
+        let env: Env = [
+            ("TAURUS_WS_PASSWORD", "123456"),
+            ("TAURUS_WS_PORT", "7600"),
+            ("TAURUS_SERVERS__DIGITS__RCON__PORT", "25575"),
+            ("TAURUS_SERVERS__DIGITS__RCON__PASSWORD", "1234"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let config = read::<Config>(&config, false, &env).unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(config.value.ws_password, "123456");
+        assert_eq!(config.value.ws_port, 7600);
+        let session = read::<Session>(&session, true, &env).unwrap_or_else(|e| panic!("{e}"));
+        let rcon = session.value.rcon.as_ref().unwrap();
+        assert_eq!(rcon.password, "1234");
+        assert_eq!(rcon.port, 25575);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,8 +1,22 @@
 use crate::{
     backup::list_backups,
-    bridge::{Bridge, Session},
+    bridge::{Bridge, ServerState, Session},
     config::Config,
-    utils::{Clients, Result, Sys, SysDisplay, WsClient},
+    files,
+    history::History,
+    jobs,
+    list::{OnlineList, DEFAULT_LIST_CACHE_TTL},
+    map::{render_dimension, render_region, tile_folder},
+    paths,
+    playerdata::player_info,
+    playtime::PlayerStore,
+    region::Dimension,
+    scoreboard::{self, SCOREBOARD_FILE},
+    search::{find_item, Area},
+    sync::{sync_regions, ChunkArea, MAX_REGIONS},
+    trim::{trim_world, TrimOptions},
+    upload,
+    utils::{unix_millis, Clients, Result, Sys, SysDisplay, WsClient},
 };
 use futures::{FutureExt, StreamExt};
 use log::{info, warn};
@@ -22,17 +36,19 @@ use warp::{
 };
 
 lazy_static::lazy_static! {
-    static ref CONFIG_PATH: String = {
-        let path: Vec<String> = env::args().collect();
-        path[0][..path[0].len() - 6].to_string()
-    };
     pub(crate) static ref ARGS: Vec<String> = env::args().collect();
-    pub(crate) static ref PATH: String = ARGS[0].to_owned()[..ARGS[0].len() - 6].to_string();
-    pub(crate) static ref SESSIONS: Arc<RwLock<Vec<Session>>> = Arc::new(RwLock::new(Config::load_sessions(PATH.to_owned())));
-    pub(crate) static ref CONFIG: Arc<RwLock<Config>> = Arc::new(RwLock::new(Config::load_config(PATH.to_owned())));
+    pub(crate) static ref CONFIG_DIR: PathBuf = paths::config_dir(&ARGS);
+    pub(crate) static ref DATA_DIR: PathBuf = paths::data_dir(&ARGS);
+    pub(crate) static ref SESSIONS: Arc<RwLock<Vec<Session>>> = Arc::new(RwLock::new(Config::load_sessions(&CONFIG_DIR)));
+    pub(crate) static ref CONFIG: Arc<RwLock<Config>> = Arc::new(RwLock::new(Config::load_config(&CONFIG_DIR)));
     pub(crate) static ref BRIDGES: Arc<Mutex<Vec<Bridge>>> = Arc::new(Mutex::new(Vec::new()));
+    pub(crate) static ref EXPORTS_PATH: PathBuf = DATA_DIR.join("exports");
+    pub(crate) static ref HISTORY_PATH: PathBuf = DATA_DIR.join("history.json");
+    pub(crate) static ref HISTORY: Arc<Mutex<History>> = Arc::new(Mutex::new(History::load(&*HISTORY_PATH)));
+    pub(crate) static ref PLAYERS_PATH: PathBuf = DATA_DIR.join("players.json");
+    pub(crate) static ref PLAYERS: Arc<Mutex<PlayerStore>> = Arc::new(Mutex::new(PlayerStore::load(&*PLAYERS_PATH)));
     static ref RESTART_SCRIPT: Option<String> = None;
-    // Config::load_config(CONFIG_PATH.to_string()).restart_script;
+    // Config::load_config(&CONFIG_DIR).restart_script;
 }
 
 pub(crate) async fn client_connection(ws: WebSocket, clients: Clients) {
@@ -73,17 +89,14 @@ async fn client_msg(client_id: &str, msg: Message, clients: &Clients) {
     let mut locked = clients.lock().await;
     if let Some(mut v) = locked.get_mut(client_id) {
         if !v.authed {
-            v.authed = {
-                if CONFIG.read().await.ws_password.len() == msg.len() {
-                    let mut result = 0;
-                    for (x, y) in CONFIG.read().await.ws_password.chars().zip(msg.chars()) {
-                        result |= x as u32 ^ y as u32;
+            v.authed = check_password(msg).await;
+            if v.authed {
+                if let Some(limit) = CONFIG.read().await.history_replay {
+                    for event in HISTORY.lock().await.recent(limit) {
+                        v.send(event.to_message()).await;
                     }
-                    result == 0
-                } else {
-                    false
                 }
-            };
+            }
             return;
         }
     }
@@ -101,6 +114,19 @@ pub(crate) async fn ws_handler(ws: warp::ws::Ws, clients: Clients) -> Result<imp
     Ok(ws.on_upgrade(move |socket| client_connection(socket, clients)))
 }
 
+// compares every character so the time taken doesn't give away how much of it was right
+pub(crate) async fn check_password(input: &str) -> bool {
+    let config = CONFIG.read().await;
+    if config.ws_password.len() != input.len() {
+        return false;
+    }
+    let mut result = 0;
+    for (x, y) in config.ws_password.chars().zip(input.chars()) {
+        result |= x as u32 ^ y as u32;
+    }
+    result == 0
+}
+
 fn get_cmd(msg: &str) -> Option<(&str, &str)> {
     let response = match msg.find(' ') {
         Some(v) => v,
@@ -109,7 +135,7 @@ fn get_cmd(msg: &str) -> Option<(&str, &str)> {
     Some((&msg[..response], &msg[response + 1..]))
 }
 
-async fn handle_response(message: &str) -> Option<String> {
+pub(crate) async fn handle_response(message: &str) -> Option<String> {
     let command_index = message.find(' ');
 
     // split the command into the first word if applicable
@@ -124,8 +150,6 @@ async fn handle_response(message: &str) -> Option<String> {
                 None => return None,
             };
             let bridges = BRIDGES.lock().await;
-            // TODO
-            // replace with tmux json + cleanse input
             Session::send_chat_to_clients(&bridges, in_game_message).await;
             None
         }
@@ -135,22 +159,24 @@ async fn handle_response(message: &str) -> Option<String> {
                 None => return None,
             };
             let bridges = BRIDGES.lock().await;
-            // TODO
-            // replace with tmux json + cleanse input
             Session::send_url_to_clients(&bridges, in_game_message).await;
             None
         }
         "LIST" => {
-            let mut lists = Vec::new();
+            let ttl = CONFIG
+                .read()
+                .await
+                .list_cache_ttl
+                .unwrap_or(DEFAULT_LIST_CACHE_TTL);
+            let mut lists = serde_json::Map::new();
             for session in &*SESSIONS.read().await {
                 if let Some(v) = &session.rcon {
-                    lists.push(match v.rcon_send_with_response("list").await {
-                        Ok(Some(v)) => format!("{} {v}", session.name),
-                        _ => continue,
-                    });
+                    if let Some(list) = OnlineList::fetch(&session.name, v, ttl).await {
+                        lists.insert(session.name.clone(), json!(list));
+                    }
                 }
             }
-            Some(format!("LIST {}", lists.join("\n")))
+            Some(format!("LIST {}", json!(lists)))
         }
         "BACKUP" => {
             let (_, target) = match get_cmd(message) {
@@ -244,6 +270,24 @@ async fn handle_response(message: &str) -> Option<String> {
                 },
             )
         }
+        "DOWNLOAD_BACKUP" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("DOWNLOAD_BACKUP Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            if args.len() != 1 || args[0].contains('/') || args[0].starts_with('.') {
+                return Some("DOWNLOAD_BACKUP Invalid Arguments".into());
+            }
+            let backup = PathBuf::from(&*CONFIG.read().await.backup_location).join(args[0]);
+            if !backup.is_file() {
+                return Some("DOWNLOAD_BACKUP Backup not found".into());
+            }
+            Some(format!(
+                "DOWNLOAD_BACKUP {}",
+                files::signed_url("backup", args[0]).await
+            ))
+        }
         "TOGGLE_BRIDGE" => {
             let (_, args) = match get_cmd(message) {
                 Some(v) => v,
@@ -300,8 +344,8 @@ async fn handle_response(message: &str) -> Option<String> {
                 }
                 if let Some(v) = &session.rcon {
                     response = match v.rcon_send_with_response(cmd).await {
-                        Ok(Some(x)) => x,
-                        _ => continue,
+                        Ok(x) => x,
+                        Err(e) => format!("failed to reach {target}: {e}"),
                     };
                 }
             }
@@ -347,6 +391,426 @@ async fn handle_response(message: &str) -> Option<String> {
             }
             Some(format!("LIST_STRUCTURES {response}"))
         }
+        "HISTORY" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("HISTORY Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            let session = match args.first() {
+                Some(v) => *v,
+                None => return Some("HISTORY Invalid Arguments".into()),
+            };
+            let (since, limit) = match args.get(1) {
+                Some(v) => match v.strip_prefix("since:") {
+                    Some(t) => match t.parse() {
+                        Ok(t) => (Some(t), None),
+                        Err(_) => return Some("HISTORY Invalid Timestamp".into()),
+                    },
+                    None => match v.parse() {
+                        Ok(l) => (None, Some(l)),
+                        Err(_) => return Some("HISTORY Invalid Limit".into()),
+                    },
+                },
+                None => (None, None),
+            };
+            let history = HISTORY.lock().await;
+            Some(format!(
+                "HISTORY {}",
+                json!(history.query(session, since, limit))
+            ))
+        }
+        "PLAYTIME" => {
+            let (_, player) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("PLAYTIME Invalid Arguments".into()),
+            };
+            Some(format!(
+                "PLAYTIME {}",
+                PLAYERS.lock().await.playtime(player.trim(), unix_millis())
+            ))
+        }
+        "LAST_SEEN" => {
+            let (_, player) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("LAST_SEEN Invalid Arguments".into()),
+            };
+            Some(match PLAYERS.lock().await.last_seen(player.trim()) {
+                Some(v) => format!("LAST_SEEN {v}"),
+                None => "LAST_SEEN Player not found".to_owned(),
+            })
+        }
+        "TOP_PLAYTIME" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("TOP_PLAYTIME Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            let session = match args.first() {
+                Some(v) => *v,
+                None => return Some("TOP_PLAYTIME Invalid Arguments".into()),
+            };
+            let limit = match args.get(1).map(|v| v.parse()) {
+                Some(Ok(v)) => v,
+                Some(Err(_)) => return Some("TOP_PLAYTIME Invalid Limit".into()),
+                None => 10,
+            };
+            Some(format!(
+                "TOP_PLAYTIME {}",
+                PLAYERS
+                    .lock()
+                    .await
+                    .top_playtime(session, limit, unix_millis())
+            ))
+        }
+        "FIND_ITEM" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("FIND_ITEM Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            if args.len() < 3 {
+                return Some("FIND_ITEM Invalid Arguments".into());
+            }
+            let dim = match Dimension::parse(args[1]) {
+                Some(v) => v,
+                None => return Some("FIND_ITEM Invalid Dimension Provided".into()),
+            };
+            let area = match Area::parse(&args[3..]) {
+                Some(v) => v,
+                None => return Some("FIND_ITEM Invalid Area".into()),
+            };
+            let world = SESSIONS
+                .read()
+                .await
+                .iter()
+                .find(|s| s.name == args[0])
+                .and_then(|s| s.game.as_ref())
+                .and_then(|g| g.world());
+            let world = match world {
+                Some(v) => v,
+                None => return Some("FIND_ITEM Invalid Session Target".into()),
+            };
+            let item = args[2].to_string();
+            let id = jobs::spawn("find_item", args[0], move |progress| {
+                find_item(world, dim, &item, area, progress)
+            });
+            Some(format!("FIND_ITEM started job {id}"))
+        }
+        "JOB" => {
+            let id = match get_cmd(message).map(|(_, id)| id.trim().parse()) {
+                Some(Ok(v)) => v,
+                _ => return Some("JOB Invalid Arguments".into()),
+            };
+            match jobs::get(id) {
+                Some(v) => Some(format!("JOB {}", json!(v))),
+                None => Some("JOB Job not found".into()),
+            }
+        }
+        "JOBS" => Some(format!("JOBS {}", json!(jobs::list()))),
+        "SCOREBOARD_DIFF" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("SCOREBOARD_DIFF Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            if !(3..=4).contains(&args.len()) {
+                return Some("SCOREBOARD_DIFF Invalid Arguments".into());
+            }
+            let game = SESSIONS
+                .read()
+                .await
+                .iter()
+                .find(|s| s.name == args[0])
+                .and_then(|s| s.game.clone());
+            let game = match game {
+                Some(v) => v,
+                None => return Some("SCOREBOARD_DIFF Invalid Session Target".into()),
+            };
+            // compare against the live world if only one point in time is given
+            let mut snapshots = Vec::with_capacity(2);
+            for spec in [args[2], args.get(3).copied().unwrap_or("live")] {
+                match game.find_snapshot(args[0], spec).await {
+                    Some(v) => snapshots.push(v),
+                    None => return Some(format!("SCOREBOARD_DIFF No backup found for {spec}")),
+                }
+            }
+            let mut scores = Vec::with_capacity(2);
+            for snapshot in &snapshots {
+                let data = match game.read_world_file(snapshot, SCOREBOARD_FILE).await {
+                    Ok(v) => v,
+                    Err(e) => return Some(format!("SCOREBOARD_DIFF {e}")),
+                };
+                match scoreboard::scores(&data, args[1]) {
+                    Ok(v) => scores.push(v),
+                    Err(e) => return Some(format!("SCOREBOARD_DIFF {e}")),
+                }
+            }
+            if scores.iter().all(|s| s.is_none()) {
+                return Some("SCOREBOARD_DIFF Objective not found".into());
+            }
+            let (before, after) = (
+                scores[0].clone().unwrap_or_default(),
+                scores[1].clone().unwrap_or_default(),
+            );
+            Some(format!(
+                "SCOREBOARD_DIFF {}",
+                json!({
+                    "objective": args[1],
+                    "from": snapshots[0].name(),
+                    "to": snapshots[1].name(),
+                    "changes": scoreboard::diff(&before, &after),
+                })
+            ))
+        }
+        "PLAYER_INFO" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("PLAYER_INFO Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            if !(2..=3).contains(&args.len()) {
+                return Some("PLAYER_INFO Invalid Arguments".into());
+            }
+            let game = SESSIONS
+                .read()
+                .await
+                .iter()
+                .find(|s| s.name == args[0])
+                .and_then(|s| s.game.clone());
+            let game = match game {
+                Some(v) => v,
+                None => return Some("PLAYER_INFO Invalid Session Target".into()),
+            };
+            let spec = args.get(2).copied().unwrap_or("live");
+            let snapshot = match game.find_snapshot(args[0], spec).await {
+                Some(v) => v,
+                None => return Some(format!("PLAYER_INFO No backup found for {spec}")),
+            };
+            Some(match player_info(&game, args[1], &snapshot).await {
+                Ok(v) => format!("PLAYER_INFO {v}"),
+                Err(e) => format!("PLAYER_INFO {e}"),
+            })
+        }
+        "TRIM_WORLD" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("TRIM_WORLD Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            if args.len() < 2 {
+                return Some("TRIM_WORLD Invalid Arguments".into());
+            }
+            let dim = match Dimension::parse(args[1]) {
+                Some(v) => v,
+                None => return Some("TRIM_WORLD Invalid Dimension Provided".into()),
+            };
+            let options = match TrimOptions::parse(&args[2..]) {
+                Some(v) => v,
+                None => return Some("TRIM_WORLD Invalid Arguments, expected --min-inhabited <ticks> [--min-age <days>] [--dry-run]".into()),
+            };
+            let session = SESSIONS
+                .read()
+                .await
+                .iter()
+                .find(|s| s.name == args[0])
+                .cloned();
+            let (session, game) = match session {
+                Some(s) => match s.game.clone() {
+                    Some(game) => (s, game),
+                    None => return Some("TRIM_WORLD Invalid Session Target".into()),
+                },
+                None => return Some("TRIM_WORLD Invalid Session Target".into()),
+            };
+            // the server would write trimmed chunks back from memory
+            if !options.dry_run && session.server_state().await != ServerState::Stopped {
+                return Some("TRIM_WORLD Stop the server before trimming the world".into());
+            }
+            let world = match game.world() {
+                Some(v) => v,
+                None => return Some("TRIM_WORLD no configured file path".into()),
+            };
+            let handle = tokio::runtime::Handle::current();
+            let backup_location = CONFIG.read().await.backup_location.clone();
+            let name = args[0].to_string();
+            let id = jobs::spawn("trim_world", args[0], move |progress| {
+                let backup = match options.dry_run {
+                    true => None,
+                    false => Some(handle.block_on(game.backup_now(&name, &backup_location))?),
+                };
+                // the server could have been started while the backup was made
+                if !options.dry_run
+                    && handle.block_on(session.server_state()) != ServerState::Stopped
+                {
+                    return Err("the server was started, nothing was trimmed".into());
+                }
+                let mut result = trim_world(&world, dim, &options, progress)?;
+                result["backup"] = json!(backup);
+                Ok(result)
+            });
+            Some(format!("TRIM_WORLD started job {id}"))
+        }
+        "SYNC_REGIONS" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("SYNC_REGIONS Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            if args.len() != 7 || args[0] == args[1] {
+                return Some("SYNC_REGIONS Invalid Arguments".into());
+            }
+            let dim = match Dimension::parse(args[2]) {
+                Some(v) => v,
+                None => return Some("SYNC_REGIONS Invalid Dimension Provided".into()),
+            };
+            let area = match ChunkArea::parse(&args[3..]) {
+                Some(v) => v,
+                None => return Some("SYNC_REGIONS Invalid Coordinates".into()),
+            };
+            if area.region_count() > MAX_REGIONS {
+                return Some(format!(
+                    "SYNC_REGIONS Area is larger than {MAX_REGIONS} regions"
+                ));
+            }
+            let mut sessions = Vec::new();
+            for name in &args[..2] {
+                let session = SESSIONS
+                    .read()
+                    .await
+                    .iter()
+                    .find(|s| &s.name == name)
+                    .cloned();
+                let world = session.as_ref().and_then(|s| s.game.as_ref()?.world());
+                match (session, world) {
+                    (Some(session), Some(world)) => sessions.push((session, world)),
+                    _ => return Some(format!("SYNC_REGIONS Invalid Session Target {name}")),
+                }
+            }
+            // saving is paused on servers that aren't known to be stopped so neither side writes
+            // the area mid copy
+            let mut paused = Vec::new();
+            for (session, _) in &sessions {
+                if session.server_state().await == ServerState::Stopped {
+                    continue;
+                }
+                match &session.rcon {
+                    Some(v) => paused.push(v.clone()),
+                    None => {
+                        return Some(format!(
+                            "SYNC_REGIONS {} may be running without rcon, stop it first",
+                            session.name
+                        ))
+                    }
+                }
+            }
+            let (dst, src) = (sessions.pop().unwrap().1, sessions.pop().unwrap().1);
+            let handle = tokio::runtime::Handle::current();
+            let id = jobs::spawn("sync_regions", args[1], move |progress| {
+                let result = handle.block_on(async {
+                    for rcon in &paused {
+                        rcon.rcon_send("save-off").await?;
+                        rcon.rcon_send("save-all flush").await?;
+                    }
+                    Ok::<(), std::io::Error>(())
+                });
+                let result = result
+                    .map_err(|e| format!("failed to pause saving: {e}"))
+                    .and_then(|_| sync_regions(&src, &dst, dim, area, progress));
+                handle.block_on(async {
+                    for rcon in &paused {
+                        let _ = rcon.rcon_send("save-on").await;
+                    }
+                });
+                result
+            });
+            Some(format!("SYNC_REGIONS started job {id}"))
+        }
+        "UNDO_UPLOAD" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some("UNDO_UPLOAD Invalid Arguments".into()),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            if args.len() != 2 {
+                return Some("UNDO_UPLOAD Invalid Arguments".into());
+            }
+            let world = SESSIONS
+                .read()
+                .await
+                .iter()
+                .find(|s| s.name == args[0])
+                .and_then(|s| s.game.as_ref()?.world());
+            let world = match world {
+                Some(v) => v,
+                None => return Some("UNDO_UPLOAD Invalid Session Target".into()),
+            };
+            let backup_location = CONFIG.read().await.backup_location.clone();
+            match upload::undo(&world, args[0], args[1], &backup_location) {
+                Ok(v) | Err(v) => Some(format!("UNDO_UPLOAD {v}")),
+            }
+        }
+        "RENDER_REGION" | "RENDER_MAP" => {
+            let (_, args) = match get_cmd(message) {
+                Some(v) => v,
+                None => return Some(format!("{command} Invalid Arguments")),
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            let region = match (command, args.len()) {
+                ("RENDER_REGION", 4) => match (args[2].parse(), args[3].parse()) {
+                    (Ok(x), Ok(z)) => Some((x, z)),
+                    _ => return Some("RENDER_REGION Invalid Region Identifier".into()),
+                },
+                ("RENDER_MAP", 2) => None,
+                _ => return Some(format!("{command} Invalid Arguments")),
+            };
+            let dim = match Dimension::parse(args[1]) {
+                Some(v) => v,
+                None => return Some(format!("{command} Invalid Dimension Provided")),
+            };
+            let (location, prefix) = {
+                let config = CONFIG.read().await;
+                match (&config.webserver_location, &config.webserver_prefix) {
+                    (Some(l), Some(p)) => (l.to_owned(), p.to_owned()),
+                    _ => return Some(format!("{command} webserver not configured")),
+                }
+            };
+            let world = SESSIONS
+                .read()
+                .await
+                .iter()
+                .find(|s| s.name == args[0])
+                .and_then(|s| s.game.as_ref())
+                .and_then(|g| g.world());
+            let world = match world {
+                Some(v) => v,
+                None => return Some(format!("{command} Invalid Session Target")),
+            };
+            let tiles = tile_folder(args[0], dim);
+            let folder = PathBuf::from(location).join(&tiles);
+            let url = format!("{prefix}/{tiles}");
+            // both run as jobs, this is called with the clients locked
+            Some(match region {
+                Some((x, z)) => {
+                    let id = jobs::spawn("render_region", args[0], move |_| {
+                        let drawn = render_region(&world, dim, x, z, &folder)?;
+                        Ok(json!({
+                            "dimension": dim.name(),
+                            "chunks_drawn": drawn,
+                            "url": format!("{url}/r.{x}.{z}.png"),
+                        }))
+                    });
+                    format!("RENDER_REGION started job {id}")
+                }
+                None => {
+                    let id = jobs::spawn("render_map", args[0], move |progress| {
+                        let mut result = render_dimension(&world, dim, &folder, progress)?;
+                        result["url"] = json!(url);
+                        Ok(result)
+                    });
+                    format!("RENDER_MAP started job {id}")
+                }
+            })
+        }
         "LIST_BACKUPS" => Some(format!(
             "LIST_BACKUPS {}",
             list_backups(&*SESSIONS.read().await).await
@@ -369,7 +833,12 @@ async fn handle_response(message: &str) -> Option<String> {
         }
         "LIST_SESSIONS" => Some(format!(
             "LIST_SESSIONS {}",
-            json!(*SESSIONS.read().await.clone())
+            json!(SESSIONS
+                .read()
+                .await
+                .iter()
+                .map(|s| s.redacted())
+                .collect::<Vec<_>>())
         )),
         "SHELL" => {
             let instructions: Vec<&str> =